@@ -0,0 +1,99 @@
+#import bevy_render::view::View
+#import line_racing::lines::{PackedLine, clip_near, clip_to_pixels, line_end, line_start}
+
+@group(0) @binding(0) var<uniform> view: View;
+
+// The closest depth of the lines in every pixel, row by row from the top left, as the bits of a
+// float. Depth is reversed, so the largest value is the closest, and 0 means no line.
+#ifdef BLIT
+@group(1) @binding(0) var<storage, read> pixels: array<u32>;
+#else
+@group(1) @binding(0) var<storage, read> lines: array<PackedLine>;
+@group(1) @binding(1) var<storage, read_write> pixels: array<atomic<u32>>;
+#endif
+
+fn viewport_size() -> vec2<f32> {
+    return view.viewport.zw;
+}
+
+#ifndef BLIT
+// Pixel coordinates from the top left of the viewport, and depth.
+fn to_pixels(clip: vec4<f32>) -> vec3<f32> {
+    return vec3(clip_to_pixels(clip, vec4(vec2(0.0), viewport_size())), clip.z / clip.w);
+}
+
+@compute @workgroup_size(64)
+fn rasterize(
+    @builtin(global_invocation_id) id: vec3<u32>,
+    @builtin(num_workgroups) groups: vec3<u32>,
+) {
+    let index = id.x + id.y * groups.x * 64u;
+    if index >= arrayLength(&lines) {
+        return;
+    }
+    let line = clip_near(
+        view.clip_from_world * vec4(line_start(lines[index]), 1.0),
+        view.clip_from_world * vec4(line_end(lines[index]), 1.0),
+    );
+    if !line.visible {
+        return;
+    }
+
+    let start = to_pixels(line.start);
+    let delta = to_pixels(line.end) - start;
+
+    // Clip to the viewport (Liang–Barsky)
+    let size = viewport_size();
+    var t0 = 0.0;
+    var t1 = 1.0;
+    var p = array(-delta.x, delta.x, -delta.y, delta.y);
+    var q = array(start.x, size.x - start.x, start.y, size.y - start.y);
+    for (var i = 0; i < 4; i++) {
+        if p[i] == 0.0 {
+            if q[i] < 0.0 {
+                return;
+            }
+        } else {
+            let t = q[i] / p[i];
+            if p[i] < 0.0 {
+                t0 = max(t0, t);
+            } else {
+                t1 = min(t1, t);
+            }
+        }
+    }
+    if t0 > t1 {
+        return;
+    }
+    let clipped_start = start + delta * t0;
+    let clipped_delta = delta * (t1 - t0);
+
+    // DDA, one pixel per step along the major axis
+    let steps = u32(ceil(max(abs(clipped_delta.x), abs(clipped_delta.y))));
+    let width = u32(size.x);
+    let height = u32(size.y);
+    for (var i = 0u; i <= steps; i++) {
+        let point = clipped_start + clipped_delta * (f32(i) / f32(max(steps, 1u)));
+        let pixel = min(vec2<u32>(point.xy), vec2(width - 1u, height - 1u));
+        let depth = max(bitcast<u32>(max(point.z, 0.0)), 1u);
+        atomicMax(&pixels[pixel.y * width + pixel.x], depth);
+    }
+}
+#endif
+
+#ifdef BLIT
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+}
+
+@fragment
+fn blit(@builtin(position) position: vec4<f32>) -> FragmentOutput {
+    let pixel = vec2<u32>(position.xy - view.viewport.xy);
+    let depth = pixels[pixel.y * u32(view.viewport.z) + pixel.x];
+    if depth == 0u {
+        discard;
+    }
+    return FragmentOutput(vec4(1.0), bitcast<f32>(depth));
+}
+#endif
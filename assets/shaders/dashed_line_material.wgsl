@@ -0,0 +1,73 @@
+#import bevy_pbr::{
+    mesh_functions::{get_world_from_local, mesh_position_local_to_clip},
+    mesh_view_bindings::view,
+}
+#import line_racing::lines::{clip_near, clip_to_pixels}
+
+struct DashedLineMaterial {
+    color: vec4<f32>,
+    scale: f32,
+};
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(0) var<uniform> material: DashedLineMaterial;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    @location(0) position: vec3<f32>,
+#ifndef SCREEN_UNITS
+    @location(1) distance: f32,
+#endif
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+#ifdef SCREEN_UNITS
+    // Start of the line in clip space. Flat, so every pixel gets it from the first vertex of
+    // its line.
+    @location(0) @interpolate(flat) start: vec4<f32>,
+    // Position of the pixel on the line in clip space
+    @location(1) clip: vec4<f32>,
+#else
+    @location(0) distance: f32,
+#endif
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    let world_from_local = get_world_from_local(vertex.instance_index);
+    var out: VertexOutput;
+    out.clip_position = mesh_position_local_to_clip(world_from_local, vec4(vertex.position, 1.0));
+#ifdef SCREEN_UNITS
+    out.start = out.clip_position;
+    out.clip = out.clip_position;
+#else
+    out.distance = vertex.distance;
+#endif
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef SCREEN_UNITS
+    // Measured on screen from the start of the line, so the steps are the same length in
+    // pixels all along it. Lines that start behind the camera are measured from where they
+    // cross the near plane, the same place the wide lines are cut.
+    let start = clip_to_pixels(clip_near(in.start, in.clip).start, view.viewport);
+    let step = distance(in.clip_position.xy, start) / material.scale;
+#else
+    let step = in.distance / material.scale;
+#endif
+
+#ifdef PATTERN_DOT
+    let visible = step % 2.0 < 1.0;
+#else ifdef PATTERN_DASH_DOT
+    let s = step % 9.0;
+    let visible = s < 4.0 || (s >= 6.0 && s < 7.0);
+#else
+    let visible = step % 6.0 < 4.0;
+#endif
+    if !visible {
+        discard;
+    }
+    return material.color;
+}
@@ -0,0 +1,36 @@
+#import bevy_render::view::View
+#import line_racing::lines::{COLLAPSED, clip_near, expand}
+
+@group(0) @binding(0) var<uniform> view: View;
+
+struct InstancedLines {
+    width: f32,
+};
+
+@group(1) @binding(0) var<uniform> lines: InstancedLines;
+
+struct Vertex {
+    // Along the line from 0 to 1, and to which side of it from -1 to 1
+    @location(0) quad: vec2<f32>,
+    @location(1) start: vec3<f32>,
+    @location(2) end: vec3<f32>,
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> @builtin(position) vec4<f32> {
+    let line = clip_near(
+        view.clip_from_world * vec4(vertex.start, 1.0),
+        view.clip_from_world * vec4(vertex.end, 1.0),
+    );
+    if !line.visible {
+        return COLLAPSED;
+    }
+    let clip = mix(line.start, line.end, vertex.quad.x);
+    // Half the width to either side
+    return expand(line, clip, 0.0, vertex.quad.y * lines.width * 0.5, view.viewport.zw);
+}
+
+@fragment
+fn fragment() -> @location(0) vec4<f32> {
+    return vec4(1.0);
+}
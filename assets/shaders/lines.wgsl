@@ -0,0 +1,66 @@
+// Helpers shared by the line shaders, for clipping lines and expanding them on screen.
+#define_import_path line_racing::lines
+
+// Both ends of a line, as the 24 bytes the line buffers hold per line. Not two `vec3`s, since
+// those are padded to 16 bytes.
+struct PackedLine {
+    start: array<f32, 3>,
+    end: array<f32, 3>,
+};
+
+fn line_start(line: PackedLine) -> vec3<f32> {
+    return vec3(line.start[0], line.start[1], line.start[2]);
+}
+
+fn line_end(line: PackedLine) -> vec3<f32> {
+    return vec3(line.end[0], line.end[1], line.end[2]);
+}
+
+// Ends of lines closer than this in clip space w are moved along the line to it, so dividing
+// by w doesn't flip them to the other side of the screen
+const NEAR_W: f32 = 1e-4;
+
+// Zero area, so the triangles of lines that aren't drawn are skipped
+const COLLAPSED: vec4<f32> = vec4(0.0, 0.0, 0.0, 1.0);
+
+// A line in clip space, with its ends in front of the near plane
+struct ClipLine {
+    start: vec4<f32>,
+    end: vec4<f32>,
+    // False when the whole line is behind the near plane
+    visible: bool,
+};
+
+// Moves whichever end of the line is behind the near plane along the line up to it
+fn clip_near(start: vec4<f32>, end: vec4<f32>) -> ClipLine {
+    var line = ClipLine(start, end, true);
+    if start.w < NEAR_W && end.w < NEAR_W {
+        line.visible = false;
+    } else if start.w < NEAR_W {
+        line.start = mix(start, end, (NEAR_W - start.w) / (end.w - start.w));
+    } else if end.w < NEAR_W {
+        line.end = mix(end, start, (NEAR_W - end.w) / (start.w - end.w));
+    }
+    return line;
+}
+
+// Converts from clip space to framebuffer pixels, which start at the top left of the
+// framebuffer rather than the viewport
+fn clip_to_pixels(clip: vec4<f32>, viewport: vec4<f32>) -> vec2<f32> {
+    let ndc = clip.xy / clip.w;
+    return viewport.xy + (vec2(ndc.x, -ndc.y) * 0.5 + 0.5) * viewport.zw;
+}
+
+// Moves `clip`, a point on a clipped line, `along` pixels in the direction of the line on
+// screen and `across` pixels to its side. Measured in pixels, so widths are the same whatever
+// the aspect ratio.
+fn expand(line: ClipLine, clip: vec4<f32>, along: f32, across: f32, viewport_size: vec2<f32>) -> vec4<f32> {
+    let delta = line.end.xy / line.end.w * viewport_size - line.start.xy / line.start.w * viewport_size;
+    let length = length(delta);
+    // Lines pointing straight at the camera have no direction, so they get an arbitrary one
+    let direction = select(vec2(1.0, 0.0), delta / length, length > 1e-6);
+    let normal = vec2(-direction.y, direction.x);
+    let offset = direction * along + normal * across;
+    // Converted back to clip space, where the viewport is 2 units across
+    return vec4(clip.xy + offset * 2.0 / viewport_size * clip.w, clip.zw);
+}
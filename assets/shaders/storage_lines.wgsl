@@ -0,0 +1,19 @@
+#import bevy_render::view::View
+#import line_racing::lines::{PackedLine, line_end, line_start}
+
+@group(0) @binding(0) var<uniform> view: View;
+
+@group(1) @binding(0) var<storage, read> lines: array<PackedLine>;
+
+@vertex
+fn vertex(@builtin(vertex_index) vertex_index: u32) -> @builtin(position) vec4<f32> {
+    // Two vertices per line
+    let line = lines[vertex_index / 2u];
+    let position = select(line_start(line), line_end(line), vertex_index % 2u == 1u);
+    return view.clip_from_world * vec4(position, 1.0);
+}
+
+@fragment
+fn fragment() -> @location(0) vec4<f32> {
+    return vec4(1.0);
+}
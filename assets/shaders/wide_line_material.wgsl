@@ -0,0 +1,81 @@
+#import bevy_pbr::{
+    mesh_functions::{get_tag, get_world_from_local, mesh_position_local_to_clip},
+    mesh_view_bindings::view,
+}
+#import line_racing::lines::{
+    COLLAPSED, PackedLine, clip_near, clip_to_pixels, expand, line_end, line_start,
+}
+
+struct WideLineMaterial {
+    color: vec4<f32>,
+    width: f32,
+};
+
+@group(#{MATERIAL_BIND_GROUP}) @binding(0) var<uniform> material: WideLineMaterial;
+@group(#{MATERIAL_BIND_GROUP}) @binding(1) var<storage, read> lines: array<PackedLine>;
+
+struct Vertex {
+    @builtin(instance_index) instance_index: u32,
+    // Along the line from 0 to 1, to which side of it from -1 to 1, and which line of the block
+    @location(0) quad: vec3<f32>,
+};
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+#ifdef ANTIALIAS
+    // Both ends of the line in framebuffer pixels
+    @location(0) @interpolate(flat) line: vec4<f32>,
+#endif
+};
+
+@vertex
+fn vertex(vertex: Vertex) -> VertexOutput {
+    var out: VertexOutput;
+    out.clip_position = COLLAPSED;
+
+    // The tag is the first line of the block, and the last block can run past the end
+    let index = get_tag(vertex.instance_index) + u32(vertex.quad.z);
+    if index >= arrayLength(&lines) {
+        return out;
+    }
+    let world_from_local = get_world_from_local(vertex.instance_index);
+    let line = clip_near(
+        mesh_position_local_to_clip(world_from_local, vec4(line_start(lines[index]), 1.0)),
+        mesh_position_local_to_clip(world_from_local, vec4(line_end(lines[index]), 1.0)),
+    );
+    if !line.visible {
+        return out;
+    }
+    let clip = select(line.start, line.end, vertex.quad.x > 0.5);
+
+    // Half the width to either side
+    var along = 0.0;
+    var across = vertex.quad.y * material.width * 0.5;
+#ifdef ANTIALIAS
+    // Room for the faded edge around the line, and for its round ends
+    let padding = material.width * 0.5 + 1.0;
+    along = (vertex.quad.x * 2.0 - 1.0) * padding;
+    across = vertex.quad.y * padding;
+#endif
+    out.clip_position = expand(line, clip, along, across, view.viewport.zw);
+#ifdef ANTIALIAS
+    out.line = vec4(clip_to_pixels(line.start, view.viewport), clip_to_pixels(line.end, view.viewport));
+#endif
+    return out;
+}
+
+@fragment
+fn fragment(in: VertexOutput) -> @location(0) vec4<f32> {
+#ifdef ANTIALIAS
+    // Coverage of the pixel by the line, from the distance of its center to the segment
+    let a = in.line.xy;
+    let b = in.line.zw;
+    let ab = b - a;
+    let t = clamp(dot(in.clip_position.xy - a, ab) / max(dot(ab, ab), 1e-6), 0.0, 1.0);
+    let distance = length(in.clip_position.xy - (a + ab * t));
+    let coverage = clamp(material.width * 0.5 + 0.5 - distance, 0.0, 1.0);
+    return vec4(material.color.rgb, material.color.a * coverage);
+#else
+    return material.color;
+#endif
+}
@@ -1,6 +1,23 @@
+//! The scenes that draw static lines with Bevy's own meshes and materials, gizmos,
+//! bevy_polyline and bevy_vector_shapes. The `bevy_lines_example_retained` variants each change
+//! one thing about the plain [`LineList`] mesh to measure what it costs or saves:
+//! - `_colored` adds a color per vertex with [`LineColors`], colored by height.
+//! - `_strip` draws a [`LineStrip`], to compare with the continuous polyline gizmo and
+//!   bevy_polyline methods. Only the `walk` source connects its lines.
+//! - `_indexed` shares endpoints through an [`IndexedLineList`].
+//! - `_wide` and `_aa` draw a [`WideLineList`] `--line-width` pixels wide, to compare with
+//!   gizmos and bevy_polyline at the same width. `_aa` fades the edges out instead of relying
+//!   on `--msaa`, which gives smooth lines with round ends.
+//! - `_dashed` draws a `--line-pattern` with [`DashedLineMaterial`], to compare with
+//!   `gizmos_retained_combined_dashed`. Gizmos measure their patterns in line widths on screen
+//!   and have no dash-dot style.
+//! - `_chunked` splits the lines into [`LineChunks`] of `--chunk-size`, so Bevy can frustum
+//!   cull every chunk on its own. That only pays off when part of the lines is out of view, as
+//!   with `--camera-path fly-through`.
+
 use core::f32;
 
-use bevy::{math::vec3, prelude::*};
+use bevy::{math::vec3, prelude::*, render::storage::ShaderStorageBuffer};
 use bevy_mod_mesh_tools::{mesh_append, mesh_empty_default, mesh_with_transform};
 use bevy_polyline::{
     material::PolylineMaterialHandle,
@@ -10,53 +27,225 @@ use bevy_polyline::{
 use bevy_vector_shapes::prelude::*;
 
 use crate::{
-    bevy_lines_example::{LineList, LineMaterial},
-    sampling::ContinuousRandomLineGenerator,
-    LineCount, RetainedLines, UpdateCountEvent,
+    bevy_lines_example::{
+        DashedLineMaterial, IndexedLineList, LineChunks, LineColors, LineList, LineMaterial,
+        LineStrip, PatternUnits, WideLineList, WideLineMaterial,
+    },
+    methods::{MethodKind, MethodRegistry},
+    ChunkSize, LineCount, LineGenSettings, LinePatternSettings, LineWidth, RetainedLines,
+    UpdateCountEvent, VectorShapesLaa,
 };
 
+pub fn register_methods(registry: &mut MethodRegistry) {
+    registry
+        .register(
+            "bevy_lines_example_retained",
+            MethodKind::Retained,
+            "One LineList mesh drawn with a custom material",
+            bevy_lines_example_retained,
+        )
+        .register(
+            "bevy_lines_example_retained_colored",
+            MethodKind::Retained,
+            "LineList mesh with a color per vertex",
+            bevy_lines_example_retained_colored,
+        )
+        .register(
+            "bevy_lines_example_retained_strip",
+            MethodKind::Retained,
+            "LineStrip mesh, restarting the strip where lines don't connect",
+            bevy_lines_example_retained_strip,
+        )
+        .register(
+            "bevy_lines_example_retained_indexed",
+            MethodKind::Retained,
+            "Indexed LineList meshes sharing the vertices of connected lines",
+            bevy_lines_example_retained_indexed,
+        )
+        .register(
+            "bevy_lines_example_retained_wide",
+            MethodKind::Retained,
+            "Quads pulling their lines from a storage buffer, expanded to --line-width pixels",
+            bevy_lines_example_retained_wide,
+        )
+        .with_line_width()
+        .with_max_line_count(WideLineList::max_lines)
+        .register(
+            "bevy_lines_example_retained_aa",
+            MethodKind::Retained,
+            "Wide line quads faded out by distance to the line and alpha blended",
+            bevy_lines_example_retained_aa,
+        )
+        .with_line_width()
+        .with_max_line_count(WideLineList::max_lines)
+        .register(
+            "bevy_lines_example_retained_dashed",
+            MethodKind::Retained,
+            "LineList mesh with a distance attribute for --line-pattern",
+            bevy_lines_example_retained_dashed,
+        )
+        .with_pattern()
+        .register(
+            "bevy_lines_example_retained_chunked",
+            MethodKind::Retained,
+            "LineList meshes per --chunk-size grid cell, frustum culled separately",
+            bevy_lines_example_retained_chunked,
+        )
+        .with_chunks()
+        .register(
+            "bevy_plane_3d_retained",
+            MethodKind::Retained,
+            "One plane mesh entity per line",
+            bevy_plane_3d_retained,
+        )
+        .register(
+            "bevy_plane_3d_retained_combined",
+            MethodKind::Retained,
+            "Planes for every line combined into one mesh",
+            bevy_plane_3d_retained_combined,
+        )
+        .register(
+            "gizmos_immediate",
+            MethodKind::Immediate,
+            "One Gizmos::line call per line",
+            gizmos_immediate,
+        )
+        .with_line_width()
+        .register(
+            "gizmos_immediate_nan",
+            MethodKind::Immediate,
+            "One Gizmos::linestrip with lines separated by NaN",
+            gizmos_immediate_nan,
+        )
+        .with_line_width()
+        .register(
+            "gizmos_immediate_continuous_polyline",
+            MethodKind::Immediate,
+            "One Gizmos::linestrip through every vertex",
+            gizmos_immediate_continuous_polyline,
+        )
+        .with_line_width()
+        .register(
+            "gizmos_retained",
+            MethodKind::Retained,
+            "One GizmoAsset entity per line",
+            gizmos_retained,
+        )
+        .with_line_width()
+        .register(
+            "gizmos_retained_combined",
+            MethodKind::Retained,
+            "One GizmoAsset containing every line",
+            gizmos_retained_combined,
+        )
+        .with_line_width()
+        .register(
+            "gizmos_retained_combined_dashed",
+            MethodKind::Retained,
+            "One GizmoAsset containing every line, with the closest line style to --line-pattern",
+            gizmos_retained_combined_dashed,
+        )
+        .with_pattern()
+        .with_line_width()
+        .register(
+            "gizmos_retained_continuous_polyline",
+            MethodKind::Retained,
+            "One GizmoAsset linestrip through every vertex",
+            gizmos_retained_continuous_polyline,
+        )
+        .with_line_width()
+        .register(
+            "bevy_vector_shapes_retained",
+            MethodKind::Retained,
+            "One bevy_vector_shapes line entity per line",
+            bevy_vector_shapes_retained,
+        )
+        .register(
+            "bevy_vector_shapes_immediate",
+            MethodKind::Immediate,
+            "One ShapePainter::line call per line",
+            bevy_vector_shapes_immediate,
+        )
+        .register(
+            "bevy_polyline_retained",
+            MethodKind::Retained,
+            "One Polyline entity per line",
+            bevy_polyline_retained,
+        )
+        .with_line_width()
+        .register(
+            "bevy_polyline_retained_nan",
+            MethodKind::Retained,
+            "One Polyline with lines separated by NaN",
+            bevy_polyline_retained_nan,
+        )
+        .with_line_width()
+        .register(
+            "bevy_polyline_retained_continuous_polyline",
+            MethodKind::Retained,
+            "One Polyline through every vertex",
+            bevy_polyline_retained_continuous_polyline,
+        )
+        .with_line_width();
+}
+
 pub fn bevy_vector_shapes_retained(
     mut shapes: ShapeCommands,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    laa: Res<VectorShapesLaa>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
     };
     shapes.thickness = 0.002;
     shapes.cap = Cap::None;
-    shapes.disable_laa = true;
+    shapes.disable_laa = !laa.0;
 
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         shapes.line(line.0, line.1).insert(RetainedLines);
     }
 }
 
-pub fn bevy_vector_shapes_immediate(mut shapes: ShapePainter, count: Res<LineCount>) {
+pub fn bevy_vector_shapes_immediate(
+    mut shapes: ShapePainter,
+    count: Res<LineCount>,
+    line_gen_settings: Res<LineGenSettings>,
+    laa: Res<VectorShapesLaa>,
+) {
     shapes.thickness = 0.002;
     shapes.cap = Cap::None;
-    shapes.disable_laa = true;
+    shapes.disable_laa = !laa.0;
 
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         shapes.line(line.0, line.1);
     }
 }
 
-pub fn gizmos_immediate(mut gizmos: Gizmos, count: Res<LineCount>) {
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+pub fn gizmos_immediate(
+    mut gizmos: Gizmos,
+    count: Res<LineCount>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         gizmos.line(line.0, line.1, Color::WHITE);
     }
 }
 
-pub fn gizmos_immediate_nan(mut gizmos: Gizmos, count: Res<LineCount>) {
+pub fn gizmos_immediate_nan(
+    mut gizmos: Gizmos,
+    count: Res<LineCount>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
     // Draws a single polyline (instead of individual lines) and inserts a NaN in between lines to separate them.
     let mut vertices = Vec::with_capacity(count.0 as usize * 3);
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         vertices.push(line.0);
@@ -66,10 +255,14 @@ pub fn gizmos_immediate_nan(mut gizmos: Gizmos, count: Res<LineCount>) {
     gizmos.linestrip(vertices.clone(), Color::WHITE)
 }
 
-pub fn gizmos_immediate_continuous_polyline(mut gizmos: Gizmos, count: Res<LineCount>) {
+pub fn gizmos_immediate_continuous_polyline(
+    mut gizmos: Gizmos,
+    count: Res<LineCount>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
     // Draws a single polyline (instead of individual lines).
     let mut vertices = Vec::with_capacity(count.0 as usize);
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_vert();
         vertices.push(line);
@@ -82,13 +275,15 @@ pub fn bevy_polyline_retained_continuous_polyline(
     mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
     mut polylines: ResMut<Assets<Polyline>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
     };
     // Draws a single polyline (instead of individual lines).
     let mut vertices = Vec::with_capacity(count.0 as usize);
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         vertices.push(line.0);
@@ -97,7 +292,7 @@ pub fn bevy_polyline_retained_continuous_polyline(
         .spawn(PolylineBundle {
             polyline: PolylineHandle(polylines.add(Polyline { vertices })),
             material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
-                width: 1.0,
+                width: line_width.0,
                 color: LinearRgba::WHITE,
                 perspective: false,
                 ..default()
@@ -112,13 +307,15 @@ pub fn bevy_polyline_retained_nan(
     mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
     mut polylines: ResMut<Assets<Polyline>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
     };
     // Draws a single polyline (instead of individual lines) and inserts a NaN in between lines to separate them.
     let mut vertices = Vec::with_capacity(count.0 as usize * 3);
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         vertices.push(line.0);
@@ -129,7 +326,7 @@ pub fn bevy_polyline_retained_nan(
         .spawn(PolylineBundle {
             polyline: PolylineHandle(polylines.add(Polyline { vertices })),
             material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
-                width: 1.0,
+                width: line_width.0,
                 color: LinearRgba::WHITE,
                 perspective: false,
                 ..default()
@@ -144,17 +341,19 @@ pub fn bevy_polyline_retained(
     mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
     mut polylines: ResMut<Assets<Polyline>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
     };
     let material = polyline_materials.add(PolylineMaterial {
-        width: 1.0,
+        width: line_width.0,
         color: LinearRgba::WHITE,
         perspective: false,
         ..default()
     });
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         commands
@@ -174,12 +373,13 @@ pub fn bevy_lines_example_retained(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<LineMaterial>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
     };
     let mut lines = Vec::with_capacity(count.0 as usize * 2);
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         lines.push(line);
@@ -187,7 +387,71 @@ pub fn bevy_lines_example_retained(
     // Spawn a list of lines with start and end points for each lines
     commands
         .spawn((
-            Mesh3d(meshes.add(LineList { lines })),
+            Mesh3d(meshes.add(LineList { lines, ..default() })),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            MeshMaterial3d(materials.add(LineMaterial {
+                color: LinearRgba::WHITE,
+            })),
+        ))
+        .insert(RetainedLines);
+}
+
+pub fn bevy_lines_example_retained_colored(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut lines = Vec::with_capacity(count.0 as usize);
+    let mut colors = Vec::with_capacity(count.0 as usize);
+    let mut line_gen = line_gen_settings.generator(count.0);
+    // Color by height like a heatmap, kept bright so captures still see every line
+    let radius = line_gen_settings.walk.radius;
+    let center = line_gen_settings.walk.start.y;
+    let heat = |p: Vec3| {
+        let t = ((p.y - center) / (radius * 2.0) + 0.5).clamp(0.0, 1.0);
+        LinearRgba::rgb(0.2 + 0.8 * t, 0.6, 1.0 - 0.8 * t)
+    };
+    for _ in 0..count.0 {
+        let line = line_gen.next_line();
+        colors.push((heat(line.0), heat(line.1)));
+        lines.push(line);
+    }
+    commands
+        .spawn((
+            Mesh3d(meshes.add(LineList {
+                lines,
+                colors: Some(LineColors::PerVertex(colors)),
+                ..default()
+            })),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            MeshMaterial3d(materials.add(LineMaterial {
+                color: LinearRgba::WHITE,
+            })),
+        ))
+        .insert(RetainedLines);
+}
+
+pub fn bevy_lines_example_retained_strip(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    // The random walk makes a single strip, other sources break it up
+    let strip = LineStrip::from_lines((0..count.0).map(|_| line_gen.next_line()));
+    commands
+        .spawn((
+            Mesh3d(meshes.add(strip)),
             Transform::from_xyz(0.0, 0.0, 0.0),
             MeshMaterial3d(materials.add(LineMaterial {
                 color: LinearRgba::WHITE,
@@ -196,11 +460,185 @@ pub fn bevy_lines_example_retained(
         .insert(RetainedLines);
 }
 
+pub fn bevy_lines_example_retained_indexed(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = IndexedLineList::from_lines((0..count.0).map(|_| line_gen.next_line()));
+    let material = materials.add(LineMaterial {
+        color: LinearRgba::WHITE,
+    });
+    // One mesh per 65536 vertices, so every mesh can use u16 indices
+    for mesh in lines.into_meshes() {
+        commands
+            .spawn((
+                Mesh3d(meshes.add(mesh)),
+                Transform::from_xyz(0.0, 0.0, 0.0),
+                MeshMaterial3d(material.clone()),
+            ))
+            .insert(RetainedLines);
+    }
+}
+
+pub fn bevy_lines_example_retained_wide(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<WideLineMaterial>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = WideLineList {
+        lines: (0..count.0).map(|_| line_gen.next_line()).collect(),
+    };
+    spawn_wide_lines(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &mut buffers,
+        lines,
+        line_width.0,
+        false,
+    );
+}
+
+pub fn bevy_lines_example_retained_aa(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<WideLineMaterial>>,
+    mut buffers: ResMut<Assets<ShaderStorageBuffer>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = WideLineList {
+        lines: (0..count.0).map(|_| line_gen.next_line()).collect(),
+    };
+    spawn_wide_lines(
+        &mut commands,
+        &mut meshes,
+        &mut materials,
+        &mut buffers,
+        lines,
+        line_width.0,
+        true,
+    );
+}
+
+/// Spawns one entity per block of `lines`, all sharing a mesh and a material that reads the
+/// lines from one storage buffer.
+fn spawn_wide_lines(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    materials: &mut Assets<WideLineMaterial>,
+    buffers: &mut Assets<ShaderStorageBuffer>,
+    lines: WideLineList,
+    width: f32,
+    antialias: bool,
+) {
+    let mesh = meshes.add(WideLineList::block_mesh());
+    let material = materials.add(WideLineMaterial {
+        color: LinearRgba::WHITE,
+        width,
+        lines: buffers.add(lines.storage_buffer()),
+        antialias,
+    });
+    for tag in lines.block_tags() {
+        commands
+            .spawn((
+                Mesh3d(mesh.clone()),
+                tag,
+                Transform::from_xyz(0.0, 0.0, 0.0),
+                MeshMaterial3d(material.clone()),
+            ))
+            .insert(RetainedLines);
+    }
+}
+
+pub fn bevy_lines_example_retained_dashed(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<DashedLineMaterial>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    pattern: Res<LinePatternSettings>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = (0..count.0).map(|_| line_gen.next_line()).collect();
+    commands
+        .spawn((
+            Mesh3d(meshes.add(LineList {
+                lines,
+                distances: pattern.units == PatternUnits::World,
+                ..default()
+            })),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            MeshMaterial3d(materials.add(DashedLineMaterial {
+                color: LinearRgba::WHITE,
+                scale: pattern.scale,
+                pattern: pattern.pattern,
+                units: pattern.units,
+            })),
+        ))
+        .insert(RetainedLines);
+}
+
+pub fn bevy_lines_example_retained_chunked(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    chunk_size: Res<ChunkSize>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = (0..count.0)
+        .map(|_| line_gen.next_line())
+        .collect::<Vec<_>>();
+    let material = materials.add(LineMaterial {
+        color: LinearRgba::WHITE,
+    });
+    // Bevy computes the bounds of every chunk from its mesh
+    for chunk in LineChunks::from_lines(&lines, chunk_size.0).chunks {
+        let lines = chunk.iter().map(|&i| lines[i as usize]).collect();
+        commands
+            .spawn((
+                Mesh3d(meshes.add(LineList { lines, ..default() })),
+                Transform::from_xyz(0.0, 0.0, 0.0),
+                MeshMaterial3d(material.clone()),
+            ))
+            .insert(RetainedLines);
+    }
+}
+
 pub fn bevy_plane_3d_retained(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
@@ -214,7 +652,7 @@ pub fn bevy_plane_3d_retained(
         ..default()
     });
 
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         let n = (line.1 - line.0).normalize();
@@ -238,6 +676,7 @@ pub fn bevy_plane_3d_retained_combined(
     mut meshes: ResMut<Assets<Mesh>>,
     mut materials: ResMut<Assets<StandardMaterial>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
@@ -248,7 +687,7 @@ pub fn bevy_plane_3d_retained_combined(
     let mesh = Plane3d::default().mesh().size(0.0022, 1.01).build();
     let mut combined_mesh = mesh_empty_default();
 
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         let n = (line.1 - line.0).normalize();
@@ -281,12 +720,14 @@ pub fn gizmos_retained(
     mut commands: Commands,
     mut gizmos: ResMut<Assets<GizmoAsset>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
     };
 
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
 
     for _ in 0..count.0 {
         let line = line_gen.next_line();
@@ -299,7 +740,7 @@ pub fn gizmos_retained(
             .spawn(Gizmo {
                 handle: gizmos.add(linegizmo),
                 line_config: GizmoLineConfig {
-                    width: 1.0,
+                    width: line_width.0,
                     ..default()
                 },
                 ..default()
@@ -312,12 +753,47 @@ pub fn gizmos_retained_combined(
     mut commands: Commands,
     mut gizmos: ResMut<Assets<GizmoAsset>>,
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+
+    let mut line_gen = line_gen_settings.generator(count.0);
+
+    let mut linegizmo = GizmoAsset::default();
+
+    for _ in 0..count.0 {
+        let line = line_gen.next_line();
+        linegizmo.line(line.0, line.1, Color::WHITE);
+    }
+
+    commands
+        .spawn(Gizmo {
+            handle: gizmos.add(linegizmo),
+            line_config: GizmoLineConfig {
+                width: line_width.0,
+                ..default()
+            },
+            ..default()
+        })
+        .insert(RetainedLines);
+}
+
+pub fn gizmos_retained_combined_dashed(
+    mut commands: Commands,
+    mut gizmos: ResMut<Assets<GizmoAsset>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
+    pattern: Res<LinePatternSettings>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
     };
 
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
 
     let mut linegizmo = GizmoAsset::default();
 
@@ -330,7 +806,8 @@ pub fn gizmos_retained_combined(
         .spawn(Gizmo {
             handle: gizmos.add(linegizmo),
             line_config: GizmoLineConfig {
-                width: 1.0,
+                width: line_width.0,
+                style: pattern.gizmo_line_style(line_width.0),
                 ..default()
             },
             ..default()
@@ -343,6 +820,8 @@ pub fn gizmos_retained_continuous_polyline(
     mut gizmos: ResMut<Assets<GizmoAsset>>,
 
     mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
 ) {
     let Some(count) = update_count_event.read().last() else {
         return;
@@ -350,7 +829,7 @@ pub fn gizmos_retained_continuous_polyline(
 
     // Draws a single polyline (instead of individual lines).
     let mut vertices = Vec::with_capacity(count.0 as usize);
-    let mut line_gen = ContinuousRandomLineGenerator::default();
+    let mut line_gen = line_gen_settings.generator(count.0);
     for _ in 0..count.0 {
         let line = line_gen.next_line();
         vertices.push(line.0);
@@ -364,7 +843,7 @@ pub fn gizmos_retained_continuous_polyline(
         .spawn(Gizmo {
             handle: gizmos.add(linegizmo),
             line_config: GizmoLineConfig {
-                width: 1.0,
+                width: line_width.0,
                 ..default()
             },
             ..default()
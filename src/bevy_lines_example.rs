@@ -1,11 +1,39 @@
 //! Create a custom material to draw basic lines in 3D
 // From https://github.com/bevyengine/bevy/blob/0c7df881e72c1167060c170b09e02586fc74d85f/examples/3d/lines.rs#L1
 
+use std::collections::HashMap;
+
 use bevy::{
-    asset::RenderAssetUsages, mesh::PrimitiveTopology, prelude::*, reflect::TypePath,
-    render::render_resource::AsBindGroup, shader::ShaderRef,
+    asset::RenderAssetUsages,
+    mesh::{Indices, MeshTag, MeshVertexAttribute, MeshVertexBufferLayoutRef, PrimitiveTopology},
+    pbr::{MaterialPipeline, MaterialPipelineKey},
+    prelude::*,
+    reflect::TypePath,
+    render::{
+        render_resource::{
+            AsBindGroup, IndexFormat, RenderPipelineDescriptor, SpecializedMeshPipelineError,
+            VertexFormat, WgpuLimits,
+        },
+        storage::ShaderStorageBuffer,
+    },
+    shader::{Shader, ShaderRef},
 };
 
+/// Loads `shaders/lines.wgsl`, which the line shaders import as `line_racing::lines` for
+/// clipping lines at the near plane and expanding them on screen.
+pub struct LineShadersPlugin;
+
+impl Plugin for LineShadersPlugin {
+    fn build(&self, app: &mut App) {
+        // Imports only resolve while their module is loaded, and nothing else keeps it alive
+        let shader: Handle<Shader> = app
+            .world()
+            .resource::<AssetServer>()
+            .load("shaders/lines.wgsl");
+        std::mem::forget(shader);
+    }
+}
+
 #[derive(Asset, TypePath, Default, AsBindGroup, Debug, Clone)]
 pub struct LineMaterial {
     #[uniform(0)]
@@ -16,21 +44,511 @@ impl Material for LineMaterial {
     fn fragment_shader() -> ShaderRef {
         "shaders/line_material.wgsl".into()
     }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        _layout: &MeshVertexBufferLayoutRef,
+        _key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Strips need to know the index format for primitive restart. Setting it breaks
+        // non-indexed strips, which is why `LineStrip` always adds indices.
+        if descriptor.primitive.topology == PrimitiveTopology::LineStrip {
+            descriptor.primitive.strip_index_format = Some(IndexFormat::Uint32);
+        }
+        Ok(())
+    }
 }
 
 /// A list of lines with a start and end position
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct LineList {
     pub lines: Vec<(Vec3, Vec3)>,
+    /// Adds a color attribute, which the material multiplies with its color.
+    pub colors: Option<LineColors>,
+    /// Adds [`ATTRIBUTE_LINE_DISTANCE`], which [`DashedLineMaterial`] lays its pattern out along.
+    pub distances: bool,
 }
 
-impl From<LineList> for Mesh {
-    fn from(line: LineList) -> Self {
-        let vertices: Vec<_> = line.lines.into_iter().flat_map(|(a, b)| [a, b]).collect();
+/// Colors of the lines in a [`LineList`], in the same order as the lines.
+#[derive(Debug, Clone)]
+pub enum LineColors {
+    /// One color per line.
+    PerLine(Vec<LinearRgba>),
+    /// A start and end color per line, blended along the line.
+    PerVertex(Vec<(LinearRgba, LinearRgba)>),
+}
+
+impl LineList {
+    /// Builds the mesh, keeping it in the worlds given by `asset_usage`.
+    /// Converting with `From` only keeps it in the render world.
+    pub fn into_mesh(self, asset_usage: RenderAssetUsages) -> Mesh {
+        // Runs on along connected lines, so patterns continue around their corners, and
+        // starts over wherever a line doesn't start at the end of the previous one
+        let distances = self.distances.then(|| {
+            let mut distance = 0.0;
+            let mut previous_end = None;
+            self.lines
+                .iter()
+                .flat_map(|&(a, b)| {
+                    if previous_end != Some(a) {
+                        distance = 0.0;
+                    }
+                    let start = distance;
+                    distance += a.distance(b);
+                    previous_end = Some(b);
+                    [start, distance]
+                })
+                .collect::<Vec<f32>>()
+        });
+        let vertices: Vec<_> = self.lines.into_iter().flat_map(|(a, b)| [a, b]).collect();
         // This tells wgpu that the positions are list of lines
         // where every pair is a start and end point
-        Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList, asset_usage)
             // Add the vertices positions as an attribute
-            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices);
+        if let Some(colors) = self.colors {
+            let colors: Vec<[f32; 4]> = match colors {
+                LineColors::PerLine(colors) => colors
+                    .into_iter()
+                    .flat_map(|color| [color.to_f32_array(); 2])
+                    .collect(),
+                LineColors::PerVertex(colors) => colors
+                    .into_iter()
+                    .flat_map(|(a, b)| [a.to_f32_array(), b.to_f32_array()])
+                    .collect(),
+            };
+            mesh.insert_attribute(Mesh::ATTRIBUTE_COLOR, colors);
+        }
+        if let Some(distances) = distances {
+            mesh.insert_attribute(ATTRIBUTE_LINE_DISTANCE, distances);
+        }
+        mesh
+    }
+}
+
+impl From<LineList> for Mesh {
+    fn from(line: LineList) -> Self {
+        line.into_mesh(RenderAssetUsages::RENDER_WORLD)
+    }
+}
+
+/// Distance of each vertex of a [`LineList`] along the lines, from the start of the run of
+/// connected lines it's part of.
+pub const ATTRIBUTE_LINE_DISTANCE: MeshVertexAttribute =
+    MeshVertexAttribute::new("LineDistance", 277_013_470, VertexFormat::Float32);
+
+/// Draws a [`LineList`] with a repeating pattern of dashes and dots along the lines. The
+/// list needs `distances` set for [`PatternUnits::World`], and only then.
+#[derive(Asset, TypePath, Default, AsBindGroup, Debug, Clone)]
+#[bind_group_data(DashedLineMaterialKey)]
+pub struct DashedLineMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+    /// Length of one step of the pattern, in pixels or world units depending on `units`.
+    #[uniform(0)]
+    pub scale: f32,
+    pub pattern: LinePattern,
+    pub units: PatternUnits,
+}
+
+/// Pattern of a [`DashedLineMaterial`], in steps of its `scale`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LinePattern {
+    /// Dashes 4 steps long with gaps of 2.
+    #[default]
+    Dash,
+    /// Dots 1 step long with gaps of 1, like `GizmoLineStyle::Dotted`.
+    Dot,
+    /// A dash 4 steps long and a dot, with gaps of 2.
+    DashDot,
+}
+
+/// What the `scale` of a [`DashedLineMaterial`] is measured in.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum PatternUnits {
+    /// Pixels on screen from the start of each line, or from where it crosses the near plane
+    /// when it starts behind the camera. The pattern keeps its size on screen.
+    #[default]
+    Screen,
+    /// World units. The pattern stays in place on the lines as the camera moves.
+    World,
+}
+
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+pub struct DashedLineMaterialKey {
+    pattern: LinePattern,
+    units: PatternUnits,
+}
+
+impl From<&DashedLineMaterial> for DashedLineMaterialKey {
+    fn from(material: &DashedLineMaterial) -> Self {
+        Self {
+            pattern: material.pattern,
+            units: material.units,
+        }
+    }
+}
+
+impl Material for DashedLineMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/dashed_line_material.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/dashed_line_material.wgsl".into()
+    }
+
+    // The prepass shaders would draw the gaps
+    fn enable_prepass() -> bool {
+        false
+    }
+
+    fn enable_shadows() -> bool {
+        false
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        // Screen units are measured from the start of each line instead
+        let vertex_layout = match key.bind_group_data.units {
+            PatternUnits::Screen => layout
+                .0
+                .get_layout(&[Mesh::ATTRIBUTE_POSITION.at_shader_location(0)])?,
+            PatternUnits::World => layout.0.get_layout(&[
+                Mesh::ATTRIBUTE_POSITION.at_shader_location(0),
+                ATTRIBUTE_LINE_DISTANCE.at_shader_location(1),
+            ])?,
+        };
+        descriptor.vertex.buffers = vec![vertex_layout];
+        let mut shader_defs = Vec::new();
+        match key.bind_group_data.pattern {
+            LinePattern::Dash => shader_defs.push("PATTERN_DASH".into()),
+            LinePattern::Dot => shader_defs.push("PATTERN_DOT".into()),
+            LinePattern::DashDot => shader_defs.push("PATTERN_DASH_DOT".into()),
+        }
+        if key.bind_group_data.units == PatternUnits::Screen {
+            shader_defs.push("SCREEN_UNITS".into());
+        }
+        descriptor.vertex.shader_defs.extend(shader_defs.clone());
+        if let Some(fragment) = descriptor.fragment.as_mut() {
+            fragment.shader_defs.extend(shader_defs);
+        }
+        Ok(())
+    }
+}
+
+/// Lines connected end to end, split into strips wherever they don't connect. The strips are
+/// separated by primitive restart indices, so connected lines take half the vertices of a
+/// [`LineList`], but lines that don't connect take a strip of their own each. wgpu's OpenGL
+/// backend doesn't enable primitive restart, so there the strips run into each other.
+#[derive(Debug, Clone, Default)]
+pub struct LineStrip {
+    pub strips: Vec<Vec<Vec3>>,
+}
+
+impl LineStrip {
+    /// Joins consecutive lines into one strip as long as each starts where the last one ended.
+    pub fn from_lines(lines: impl IntoIterator<Item = (Vec3, Vec3)>) -> Self {
+        let mut strips: Vec<Vec<Vec3>> = Vec::new();
+        for (a, b) in lines {
+            match strips.last_mut() {
+                Some(strip) if strip.last() == Some(&a) => strip.push(b),
+                _ => strips.push(vec![a, b]),
+            }
+        }
+        Self { strips }
+    }
+}
+
+impl From<LineStrip> for Mesh {
+    fn from(line: LineStrip) -> Self {
+        let strips = line
+            .strips
+            .into_iter()
+            .filter(|strip| strip.len() > 1)
+            .collect::<Vec<_>>();
+        // Always indexed, since `LineMaterial` sets the strip index format for every strip.
+        // The strips are separated with the primitive restart index instead of NaN vertices.
+        let mut indices = Vec::new();
+        let mut start = 0;
+        for strip in &strips {
+            if start > 0 {
+                indices.push(u32::MAX);
+            }
+            indices.extend(start..start + strip.len() as u32);
+            start += strip.len() as u32;
+        }
+        let vertices: Vec<_> = strips.into_iter().flatten().collect();
+        Mesh::new(
+            PrimitiveTopology::LineStrip,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_indices(Indices::U32(indices))
+        .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, vertices)
+    }
+}
+
+/// A list of lines that share their endpoints through an index buffer. Connected lines upload
+/// 16 bytes per line instead of the 24 of a [`LineList`], while disconnected lines share no
+/// vertices and only gain the index buffer.
+#[derive(Debug, Clone, Default)]
+pub struct IndexedLineList {
+    pub vertices: Vec<Vec3>,
+    /// Start and end of every line, as indices into `vertices`
+    pub lines: Vec<[u32; 2]>,
+}
+
+impl IndexedLineList {
+    /// Most vertices a mesh can index with `u16` indices.
+    pub const MAX_U16_VERTICES: usize = u16::MAX as usize + 1;
+
+    /// Merges line endpoints at exactly the same position into one vertex.
+    pub fn from_lines(lines: impl IntoIterator<Item = (Vec3, Vec3)>) -> Self {
+        let mut indexed = Self::default();
+        let mut vertex_indices = HashMap::new();
+        let mut index_of = |vertices: &mut Vec<Vec3>, vertex: Vec3| {
+            *vertex_indices
+                .entry(vertex.to_array().map(f32::to_bits))
+                .or_insert_with(|| {
+                    vertices.push(vertex);
+                    vertices.len() as u32 - 1
+                })
+        };
+        for (a, b) in lines {
+            let line = [
+                index_of(&mut indexed.vertices, a),
+                index_of(&mut indexed.vertices, b),
+            ];
+            indexed.lines.push(line);
+        }
+        indexed
+    }
+
+    /// Splits the lines into meshes that each have few enough vertices for `u16` indices.
+    /// Vertices shared by lines in different meshes are duplicated.
+    pub fn into_meshes(self) -> Vec<Mesh> {
+        if self.vertices.len() <= Self::MAX_U16_VERTICES {
+            return vec![self.into()];
+        }
+        let mut meshes = Vec::new();
+        let mut chunk = Self::default();
+        let mut chunk_indices = HashMap::new();
+        for line in self.lines {
+            let new_vertices = line
+                .iter()
+                .filter(|index| !chunk_indices.contains_key(*index))
+                .count();
+            if chunk.vertices.len() + new_vertices > Self::MAX_U16_VERTICES {
+                meshes.push(std::mem::take(&mut chunk).into());
+                chunk_indices.clear();
+            }
+            let line = line.map(|index| {
+                *chunk_indices.entry(index).or_insert_with(|| {
+                    chunk.vertices.push(self.vertices[index as usize]);
+                    chunk.vertices.len() as u32 - 1
+                })
+            });
+            chunk.lines.push(line);
+        }
+        if !chunk.lines.is_empty() {
+            meshes.push(chunk.into());
+        }
+        meshes
+    }
+}
+
+impl From<IndexedLineList> for Mesh {
+    fn from(line: IndexedLineList) -> Self {
+        let indices = line.lines.into_iter().flatten();
+        // Halves the size of the index buffer when the vertices fit
+        let indices = if line.vertices.len() <= IndexedLineList::MAX_U16_VERTICES {
+            Indices::U16(indices.map(|index| index as u16).collect())
+        } else {
+            Indices::U32(indices.collect())
+        };
+        Mesh::new(PrimitiveTopology::LineList, RenderAssetUsages::RENDER_WORLD)
+            .with_inserted_indices(indices)
+            .with_inserted_attribute(Mesh::ATTRIBUTE_POSITION, line.vertices)
+    }
+}
+
+/// Lines sorted into the cells of a uniform grid by their midpoints, so every cell can be its
+/// own mesh with its own bounds.
+#[derive(Debug, Clone, Default)]
+pub struct LineChunks {
+    /// Indices of the lines in every non-empty cell, in the order the cells were first used.
+    pub chunks: Vec<Vec<u32>>,
+}
+
+impl LineChunks {
+    /// Uses cubic cells with edges `chunk_size` long, lined up with the origin.
+    pub fn from_lines(lines: &[(Vec3, Vec3)], chunk_size: f32) -> Self {
+        let mut chunks: Vec<Vec<u32>> = Vec::new();
+        let mut chunk_of_cell = HashMap::new();
+        for (i, (a, b)) in lines.iter().enumerate() {
+            let cell = ((*a + *b) * 0.5 / chunk_size).floor().as_ivec3();
+            let chunk = *chunk_of_cell.entry(cell).or_insert_with(|| {
+                chunks.push(Vec::new());
+                chunks.len() - 1
+            });
+            chunks[chunk].push(i as u32);
+        }
+        Self { chunks }
+    }
+}
+
+/// Where a vertex of [`WideLineList::block_mesh`] goes: along its line from 0 to 1, to which
+/// side of it from -1 to 1, and which line of the block it belongs to.
+pub const ATTRIBUTE_LINE_QUAD: MeshVertexAttribute =
+    MeshVertexAttribute::new("LineQuad", 277_013_468, VertexFormat::Float32x3);
+
+/// Draws a [`WideLineList`] with lines `width` pixels wide, by pushing the vertices of each
+/// line apart in screen space. Lines reaching behind the camera are cut at the near plane, so
+/// they don't flip across the screen. The whole storage buffer is bound at once, which caps the
+/// line count at the biggest storage binding the device allows, and WebGL2 has no storage
+/// buffers in vertex shaders.
+#[derive(Asset, TypePath, Default, AsBindGroup, Debug, Clone)]
+#[bind_group_data(WideLineMaterialKey)]
+pub struct WideLineMaterial {
+    #[uniform(0)]
+    pub color: LinearRgba,
+    #[uniform(0)]
+    pub width: f32,
+    /// The ends of the lines, from [`WideLineList::storage_buffer`].
+    #[storage(1, read_only)]
+    pub lines: Handle<ShaderStorageBuffer>,
+    /// Fades the edges out by the distance of each pixel to the line, and blends the lines
+    /// with what's behind them.
+    pub antialias: bool,
+}
+
+#[repr(C)]
+#[derive(Eq, PartialEq, Hash, Copy, Clone)]
+pub struct WideLineMaterialKey {
+    antialias: bool,
+}
+
+impl From<&WideLineMaterial> for WideLineMaterialKey {
+    fn from(material: &WideLineMaterial) -> Self {
+        Self {
+            antialias: material.antialias,
+        }
+    }
+}
+
+impl Material for WideLineMaterial {
+    fn vertex_shader() -> ShaderRef {
+        "shaders/wide_line_material.wgsl".into()
+    }
+
+    fn fragment_shader() -> ShaderRef {
+        "shaders/wide_line_material.wgsl".into()
+    }
+
+    fn alpha_mode(&self) -> AlphaMode {
+        if self.antialias {
+            AlphaMode::Blend
+        } else {
+            AlphaMode::Opaque
+        }
+    }
+
+    // The prepass shaders don't know about the expansion
+    fn enable_prepass() -> bool {
+        false
+    }
+
+    fn enable_shadows() -> bool {
+        false
+    }
+
+    fn specialize(
+        _pipeline: &MaterialPipeline,
+        descriptor: &mut RenderPipelineDescriptor,
+        layout: &MeshVertexBufferLayoutRef,
+        key: MaterialPipelineKey<Self>,
+    ) -> Result<(), SpecializedMeshPipelineError> {
+        if key.bind_group_data.antialias {
+            descriptor.vertex.shader_defs.push("ANTIALIAS".into());
+            if let Some(fragment) = descriptor.fragment.as_mut() {
+                fragment.shader_defs.push("ANTIALIAS".into());
+            }
+        }
+        let vertex_layout = layout
+            .0
+            .get_layout(&[ATTRIBUTE_LINE_QUAD.at_shader_location(0)])?;
+        descriptor.vertex.buffers = vec![vertex_layout];
+        // Which way the quads face depends on the direction of the line on screen
+        descriptor.primitive.cull_mode = None;
+        Ok(())
+    }
+}
+
+/// A list of lines drawn as quads, to be drawn with [`WideLineMaterial`]. The material pulls
+/// the ends of the lines from a storage buffer, so each line takes 24 bytes like in a
+/// [`LineList`]. Every entity draws the quads of [`WideLineList::BLOCK_LINES`] lines from one
+/// shared [`WideLineList::block_mesh`], starting at the line in its [`MeshTag`]. Bevy draws
+/// the blocks as instances of the same mesh.
+#[derive(Debug, Clone, Default)]
+pub struct WideLineList {
+    pub lines: Vec<(Vec3, Vec3)>,
+}
+
+impl WideLineList {
+    /// Lines drawn by each entity.
+    pub const BLOCK_LINES: u32 = 1024;
+
+    /// Most lines the material can bind at once on a device with `limits`.
+    pub fn max_lines(limits: &WgpuLimits) -> u32 {
+        let max_bytes = (limits.max_storage_buffer_binding_size as u64).min(limits.max_buffer_size);
+        (max_bytes / (2 * size_of::<Vec3>()) as u64) as u32
+    }
+
+    /// Both ends of every line as three floats each, for [`WideLineMaterial::lines`].
+    pub fn storage_buffer(&self) -> ShaderStorageBuffer {
+        let data = self
+            .lines
+            .iter()
+            .flat_map(|(a, b)| [a.to_array(), b.to_array()])
+            .flatten()
+            .flat_map(f32::to_le_bytes)
+            .collect::<Vec<_>>();
+        ShaderStorageBuffer::new(&data, RenderAssetUsages::RENDER_WORLD)
+    }
+
+    /// The tags of the entities that draw the lines, one per block.
+    pub fn block_tags(&self) -> impl Iterator<Item = MeshTag> {
+        (0..self.lines.len() as u32)
+            .step_by(Self::BLOCK_LINES as usize)
+            .map(MeshTag)
+    }
+
+    /// Quads for a block of lines. They have no positions, so the entities don't get an
+    /// `Aabb` and aren't culled. Quads past the last line are collapsed by the shader.
+    pub fn block_mesh() -> Mesh {
+        let mut quads = Vec::with_capacity(Self::BLOCK_LINES as usize * 4);
+        let mut indices = Vec::with_capacity(Self::BLOCK_LINES as usize * 6);
+        for line in 0..Self::BLOCK_LINES {
+            let line_f32 = line as f32;
+            quads.extend([
+                [0.0, 1.0, line_f32],
+                [0.0, -1.0, line_f32],
+                [1.0, 1.0, line_f32],
+                [1.0, -1.0, line_f32],
+            ]);
+            let first = line as u16 * 4;
+            indices.extend([0, 1, 2, 1, 3, 2].map(|index| first + index));
+        }
+        Mesh::new(
+            PrimitiveTopology::TriangleList,
+            RenderAssetUsages::RENDER_WORLD,
+        )
+        .with_inserted_indices(Indices::U16(indices))
+        .with_inserted_attribute(ATTRIBUTE_LINE_QUAD, quads)
     }
 }
@@ -0,0 +1,154 @@
+//! Paths the camera follows during a benchmark. Moving through or close to the lines shows how
+//! the methods differ in culling, overdraw and handling lines that cross the near plane.
+
+use std::{
+    f32::consts::{SQRT_2, TAU},
+    fs, io,
+    path::Path,
+};
+
+use bevy::{math::vec3, prelude::*};
+use serde::Deserialize;
+
+use crate::cli::{BenchOptions, BoundsArg, CameraPathArg, LineSourceArg};
+
+#[derive(Clone, Debug)]
+pub enum CameraPath {
+    /// Circles `center` at `radius`, looking at it.
+    Orbit { center: Vec3, radius: f32 },
+    /// Loops through the lines at half their `radius` from `center`, looking where it's going.
+    FlyThrough { center: Vec3, radius: f32 },
+    /// Circles `center` just outside the lines, so most of them are outside the view. `radius`
+    /// is how far the lines reach from `center` in the plane of the circle.
+    CloseUp { center: Vec3, radius: f32 },
+    /// Stays where the orbit starts.
+    Static { center: Vec3, radius: f32 },
+    /// Moves in straight lines from one keyframe to the next.
+    Keyframes(Vec<CameraKeyframe>),
+}
+
+/// A camera position in a keyframe file, which holds a JSON array of these.
+#[derive(Deserialize, Clone, Debug)]
+pub struct CameraKeyframe {
+    /// When the camera gets here. The times are stretched to the length of the benchmark, so
+    /// only how far apart they are matters.
+    pub time: f32,
+    pub position: [f32; 3],
+    /// Has to be somewhere other than `position`.
+    pub look_at: [f32; 3],
+}
+
+impl CameraPath {
+    /// The path picked by `--camera-path`, loading the keyframe file if it needs one.
+    pub fn from_options(options: &BenchOptions) -> io::Result<Self> {
+        let center = options.start;
+        let radius = options.radius;
+        Ok(match options.camera_path {
+            CameraPathArg::Orbit => CameraPath::Orbit {
+                center,
+                radius: options.orbit_radius,
+            },
+            CameraPathArg::FlyThrough => CameraPath::FlyThrough { center, radius },
+            CameraPathArg::CloseUp => CameraPath::CloseUp {
+                center,
+                // The vertical edges of a box are further out than its radius, and the grid
+                // fills a box whatever the bounds
+                radius: match (options.bounds, options.source) {
+                    (BoundsArg::Sphere, source) if source != LineSourceArg::Grid => radius,
+                    _ => radius * SQRT_2,
+                },
+            },
+            CameraPathArg::Static => CameraPath::Static {
+                center,
+                radius: options.orbit_radius,
+            },
+            CameraPathArg::Keyframes => {
+                let path = options.camera_keyframes.as_deref().ok_or_else(|| {
+                    io::Error::new(io::ErrorKind::InvalidInput, "no keyframe file given")
+                })?;
+                Self::load_keyframes(path)?
+            }
+        })
+    }
+
+    pub fn load_keyframes(path: &Path) -> io::Result<Self> {
+        let keyframes: Vec<CameraKeyframe> = serde_json::from_str(&fs::read_to_string(path)?)?;
+        if keyframes.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "the file has no keyframes",
+            ));
+        }
+        // The camera can't look at the point it's at
+        if keyframes
+            .iter()
+            .any(|keyframe| keyframe.position == keyframe.look_at)
+        {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "keyframes have to look somewhere other than their position",
+            ));
+        }
+        if keyframes.windows(2).any(|pair| pair[1].time < pair[0].time) {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "keyframe times have to be in order",
+            ));
+        }
+        Ok(CameraPath::Keyframes(keyframes))
+    }
+
+    /// Where the camera is `progress` of the way through the benchmark, from 0 to 1.
+    pub fn transform(&self, progress: f32) -> Transform {
+        let t = progress * TAU;
+        match self {
+            CameraPath::Orbit { center, radius } => {
+                Transform::from_translation(*center + vec3(t.sin(), 0.0, t.cos()) * *radius)
+                    .looking_at(*center, Vec3::Y)
+            }
+            CameraPath::FlyThrough { center, radius } => {
+                // Bobs up and down twice per loop so it doesn't stay in one plane
+                let position_at =
+                    |t: f32| *center + vec3(t.sin(), (t * 2.0).sin() * 0.5, t.cos()) * radius * 0.5;
+                Transform::from_translation(position_at(t))
+                    .looking_at(position_at(t + 0.05), Vec3::Y)
+            }
+            CameraPath::CloseUp { center, radius } => {
+                Transform::from_translation(*center + vec3(t.sin(), 0.0, t.cos()) * radius * 1.1)
+                    .looking_at(*center, Vec3::Y)
+            }
+            CameraPath::Static { center, radius } => {
+                Transform::from_translation(*center + Vec3::Z * *radius)
+                    .looking_at(*center, Vec3::Y)
+            }
+            CameraPath::Keyframes(keyframes) => {
+                let first = &keyframes[0];
+                let last = &keyframes[keyframes.len() - 1];
+                let time = first.time.lerp(last.time, progress);
+                let next = keyframes
+                    .partition_point(|keyframe| keyframe.time <= time)
+                    .min(keyframes.len() - 1);
+                let (a, b) = (&keyframes[next.saturating_sub(1)], &keyframes[next]);
+                let s = if b.time > a.time {
+                    ((time - a.time) / (b.time - a.time)).clamp(0.0, 1.0)
+                } else {
+                    1.0
+                };
+                let position = Vec3::from(a.position).lerp(b.position.into(), s);
+                let look_at = Vec3::from(a.look_at).lerp(b.look_at.into(), s);
+                Transform::from_translation(position).looking_at(look_at, Vec3::Y)
+            }
+        }
+    }
+
+    /// The name of the path as passed to `--camera-path`.
+    pub fn name(&self) -> &'static str {
+        match self {
+            CameraPath::Orbit { .. } => "orbit",
+            CameraPath::FlyThrough { .. } => "fly-through",
+            CameraPath::CloseUp { .. } => "close-up",
+            CameraPath::Static { .. } => "static",
+            CameraPath::Keyframes(_) => "keyframes",
+        }
+    }
+}
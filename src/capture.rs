@@ -0,0 +1,139 @@
+//! Captures a single frame per method and compares it against a reference method,
+//! to catch methods that win benchmarks by drawing less.
+
+use std::path::{Path, PathBuf};
+
+use bevy::{
+    camera::RenderTarget,
+    prelude::*,
+    render::view::screenshot::{Screenshot, ScreenshotCaptured},
+};
+use image::GrayImage;
+
+use crate::BenchmarkSettings;
+
+/// Where to save the captured frame.
+#[derive(Resource)]
+pub struct CapturePath(pub PathBuf);
+
+/// Takes a screenshot from the starting camera position once the scene has warmed up,
+/// saves it and exits.
+pub fn capture_frame(
+    mut commands: Commands,
+    capture_path: Res<CapturePath>,
+    settings: Res<BenchmarkSettings>,
+    time: Res<Time>,
+    target: Single<&RenderTarget, With<Camera3d>>,
+    mut warm_up_frames: Local<u32>,
+    mut requested: Local<bool>,
+) {
+    if *requested || time.elapsed_secs() < settings.warm_up {
+        return;
+    }
+    // Same extra 20 frames as the benchmark, so pipelines have time to compile
+    *warm_up_frames += 1;
+    if *warm_up_frames <= 20 {
+        return;
+    }
+    *requested = true;
+
+    let path = capture_path.0.clone();
+    commands.spawn(Screenshot((*target).clone())).observe(
+        move |captured: On<ScreenshotCaptured>, mut app_exit: MessageWriter<AppExit>| {
+            let saved = captured
+                .image
+                .clone()
+                .try_into_dynamic()
+                .map_err(|e| e.to_string())
+                .and_then(|image| image.to_rgb8().save(&path).map_err(|e| e.to_string()));
+            match saved {
+                Ok(()) => {
+                    app_exit.write(AppExit::Success);
+                }
+                Err(e) => {
+                    eprintln!("Failed to save capture to {}: {e}", path.display());
+                    app_exit.write(AppExit::error());
+                }
+            }
+        },
+    );
+}
+
+/// How a captured frame differs from the reference frame. The reference always covers some pixels.
+#[derive(Clone, Copy, Debug)]
+pub struct CaptureDiff {
+    /// Pixels covered by lines in the captured frame.
+    pub covered: u32,
+    /// Pixels covered by lines in the reference frame.
+    pub reference_covered: u32,
+    /// Pixels covered in only one of the frames, as a percentage of the pixels covered in either.
+    pub mismatch_percent: f32,
+    /// Mean absolute brightness difference over the whole frame, from 0 to 1.
+    pub mean_abs_diff: f32,
+}
+
+impl CaptureDiff {
+    /// Change in covered pixels relative to the reference, in percent.
+    pub fn coverage_change_percent(&self) -> f32 {
+        (self.covered as f32 / self.reference_covered as f32 - 1.0) * 100.0
+    }
+}
+
+pub fn compare_captures(reference: &Path, capture: &Path) -> Result<CaptureDiff, String> {
+    let load = |path: &Path| {
+        image::open(path)
+            .map(|image| image.to_luma8())
+            .map_err(|e| format!("failed to load {}: {e}", path.display()))
+    };
+    let reference = load(reference)?;
+    let capture = load(capture)?;
+    if reference.dimensions() != capture.dimensions() {
+        return Err(format!(
+            "size {:?} doesn't match the reference size {:?}",
+            capture.dimensions(),
+            reference.dimensions()
+        ));
+    }
+
+    let reference_background = background(&reference);
+    let capture_background = background(&capture);
+    let mut diff = CaptureDiff {
+        covered: 0,
+        reference_covered: 0,
+        mismatch_percent: 0.0,
+        mean_abs_diff: 0.0,
+    };
+    let mut covered_either = 0u32;
+    let mut mismatched = 0u32;
+    let mut abs_diff_sum = 0u64;
+    for (r, c) in reference.pixels().zip(capture.pixels()) {
+        let r_covered = r.0[0].abs_diff(reference_background) > COVERAGE_THRESHOLD;
+        let c_covered = c.0[0].abs_diff(capture_background) > COVERAGE_THRESHOLD;
+        diff.reference_covered += r_covered as u32;
+        diff.covered += c_covered as u32;
+        covered_either += (r_covered || c_covered) as u32;
+        mismatched += (r_covered != c_covered) as u32;
+        abs_diff_sum += r.0[0].abs_diff(c.0[0]) as u64;
+    }
+    if diff.reference_covered == 0 {
+        return Err("the reference frame has no lines in it".to_string());
+    }
+    diff.mismatch_percent = mismatched as f32 / covered_either as f32 * 100.0;
+    diff.mean_abs_diff = abs_diff_sum as f32 / (reference.len() as f32 * 255.0);
+    Ok(diff)
+}
+
+/// How far a pixel has to be from the background to count as covered by a line.
+const COVERAGE_THRESHOLD: u8 = 64;
+
+/// The most common brightness in the frame, which is the clear color unless the frame is
+/// almost completely covered in lines.
+fn background(image: &GrayImage) -> u8 {
+    let mut histogram = [0u32; 256];
+    for pixel in image.pixels() {
+        histogram[pixel.0[0] as usize] += 1;
+    }
+    (0..=255u8)
+        .max_by_key(|value| histogram[*value as usize])
+        .unwrap()
+}
@@ -0,0 +1,511 @@
+//! Command line interface for picking a rendering method and benchmarking it.
+
+use std::{fmt, path::PathBuf, str::FromStr};
+
+use bevy::{math::Vec3, render::view::Msaa, window::PresentMode};
+use clap::{builder::PossibleValuesParser, Args, Parser, Subcommand, ValueEnum};
+
+use crate::{
+    bevy_lines_example::{LinePattern, PatternUnits},
+    methods::MethodRegistry,
+    sampling::{Bounds, LineSourceKind},
+};
+
+#[derive(Parser, Debug)]
+#[command(about = "Compares different ways of drawing lots of lines with Bevy")]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: CliCommand,
+}
+
+#[derive(Subcommand, Debug)]
+pub enum CliCommand {
+    /// Draw lines using a single method. Press B to benchmark.
+    Run {
+        #[arg(value_parser = method_parser())]
+        method: String,
+        /// Start the benchmark automatically after warming up and exit when it is done.
+        #[arg(long)]
+        auto_bench: bool,
+        /// Save a PNG of the first frame after warming up to this path and exit, instead of benchmarking.
+        #[arg(long, conflicts_with = "auto_bench")]
+        capture: Option<PathBuf>,
+        #[command(flatten)]
+        options: BenchOptions,
+    },
+    /// Benchmark each method in its own process. Runs all methods if none are given.
+    Bench {
+        #[arg(value_parser = method_parser())]
+        methods: Vec<String>,
+        /// Run every method once per seed and average the results, e.g. `--seeds 1,2,3`. Overrides `--seed`.
+        #[arg(long, value_delimiter = ',')]
+        seeds: Vec<u32>,
+        #[command(flatten)]
+        options: BenchOptions,
+    },
+    /// Benchmark methods and compare their lines/ms against a saved results file.
+    /// Exits with an error code if any method regressed by more than `--max-regression`
+    /// or has no results, e.g. because it crashed.
+    Compare {
+        /// Results saved with `--output`, as JSON or CSV.
+        baseline: PathBuf,
+        /// Methods to compare. Defaults to every method in the baseline.
+        #[arg(value_parser = method_parser())]
+        methods: Vec<String>,
+        /// Largest allowed drop in lines/ms, in percent.
+        #[arg(long, default_value_t = 5.0)]
+        max_regression: f32,
+        /// Run every method once per seed and average the results, e.g. `--seeds 1,2,3`. Overrides `--seed`.
+        #[arg(long, value_delimiter = ',')]
+        seeds: Vec<u32>,
+        #[command(flatten)]
+        options: BenchOptions,
+    },
+    /// Capture the same frame with each method and compare it against a reference method,
+    /// to check that the methods draw the same lines. Captures all methods if none are given.
+    /// Exits with an error code if any method covers notably fewer pixels than the reference,
+    /// except for dashed methods, whose gaps are meant to leave pixels uncovered.
+    Capture {
+        #[arg(value_parser = method_parser())]
+        methods: Vec<String>,
+        /// Method the other captures are compared against.
+        #[arg(long, value_parser = method_parser(), default_value = "bevy_lines_example_retained")]
+        reference: String,
+        /// Directory the PNGs are saved to.
+        #[arg(long, default_value = "captures")]
+        dir: PathBuf,
+        /// Largest allowed drop in covered pixels compared to the reference, in percent.
+        #[arg(long, default_value_t = 5.0)]
+        max_coverage_loss: f32,
+        #[command(flatten)]
+        options: BenchOptions,
+    },
+    /// List the available methods.
+    List,
+}
+
+/// Only accepts the names of registered methods.
+fn method_parser() -> PossibleValuesParser {
+    PossibleValuesParser::new(MethodRegistry::default().names())
+}
+
+fn parse_positive(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if value > 0.0 && value.is_finite() => Ok(value),
+        Ok(_) => Err(format!("expected a positive number, got `{s}`")),
+        Err(e) => Err(format!("invalid number `{s}`: {e}")),
+    }
+}
+
+fn parse_percent(s: &str) -> Result<f32, String> {
+    match s.parse::<f32>() {
+        Ok(value) if (0.0..=100.0).contains(&value) => Ok(value),
+        Ok(_) => Err(format!("expected a percentage from 0 to 100, got `{s}`")),
+        Err(e) => Err(format!("invalid number `{s}`: {e}")),
+    }
+}
+
+fn parse_vec3(s: &str) -> Result<Vec3, String> {
+    let values = s
+        .split(',')
+        .map(|v| v.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("invalid vector `{s}`: {e}"))?;
+    match values[..] {
+        [x, y, z] => Ok(Vec3::new(x, y, z)),
+        _ => Err(format!("expected X,Y,Z, got `{s}`")),
+    }
+}
+
+/// Options shared by single runs and the benchmark runner.
+#[derive(Args, Debug, Clone)]
+pub struct BenchOptions {
+    /// Number of lines to draw.
+    #[arg(long, default_value_t = 150_000)]
+    pub line_count: u32,
+    /// Find the highest line count within `--frame-budget` before benchmarking. Starts at 50k
+    /// lines, doubles the count until the frame time is over the budget and then bisects.
+    #[arg(long)]
+    pub auto_count: bool,
+    /// Frame time budget of `--auto-count` in milliseconds, e.g. 16.6 for 60 fps.
+    #[arg(long, default_value_t = 8.0, value_parser = parse_positive)]
+    pub frame_budget: f32,
+    /// Stop `--auto-count` from growing the process past this much resident memory, in MiB.
+    /// Defaults to 80% of the physical memory on Linux.
+    #[arg(long)]
+    pub memory_limit: Option<u64>,
+    /// Window resolution as WIDTHxHEIGHT.
+    #[arg(long, default_value_t = Resolution::new(1024, 1024))]
+    pub resolution: Resolution,
+    /// Render into an offscreen image instead of a window, so no display server is needed.
+    /// Implies `--auto-bench` unless capturing a frame.
+    #[arg(long)]
+    pub headless: bool,
+    /// Present mode of the window.
+    #[arg(long, value_enum, default_value_t = PresentModeArg::Immediate)]
+    pub present_mode: PresentModeArg,
+    /// Seconds to warm up before the benchmark starts.
+    #[arg(long, default_value_t = 2.0)]
+    pub warm_up: f32,
+    /// Approximate number of seconds to benchmark for. At least 30 frames are always rendered.
+    #[arg(long, default_value_t = 4.0, value_parser = parse_positive)]
+    pub duration: f32,
+    /// Distribution of the lines to draw.
+    #[arg(long, value_enum, default_value_t = LineSourceArg::Walk)]
+    pub source: LineSourceArg,
+    /// Seed of the random lines.
+    #[arg(long, default_value_t = 0)]
+    pub seed: u32,
+    /// Center of the lines, as X,Y,Z. The random line walk starts here and stays within
+    /// `--radius` of it, and the other line sources are centered on it.
+    #[arg(long, default_value = "0,0,0", value_parser = parse_vec3, allow_hyphen_values = true)]
+    pub start: Vec3,
+    /// Half size of the bounds the lines stay in.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_positive)]
+    pub radius: f32,
+    /// Length of each line of the random walk and the disconnected and clustered sources.
+    #[arg(long, default_value_t = 0.03, value_parser = parse_positive)]
+    pub step_length: f32,
+    /// Shape of the bounds the lines stay in.
+    #[arg(long, value_enum, default_value_t = BoundsArg::Box)]
+    pub bounds: BoundsArg,
+    /// Width of the lines in pixels, for the methods that support it: gizmos, bevy_polyline and
+    /// the wide `LineMaterial` variant.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_positive)]
+    pub line_width: f32,
+    /// Pattern of the dashed methods.
+    #[arg(long, value_enum, default_value_t = LinePatternArg::Dash)]
+    pub line_pattern: LinePatternArg,
+    /// Units of `--pattern-scale`. Gizmos only support screen units.
+    #[arg(long, value_enum, default_value_t = PatternUnitsArg::Screen)]
+    pub pattern_units: PatternUnitsArg,
+    /// Length of one step of the line pattern, where a dash is 4 steps and a gap 2.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_positive)]
+    pub pattern_scale: f32,
+    /// Samples per pixel of multisample anti-aliasing.
+    #[arg(long, value_enum, default_value_t = MsaaArg::Off)]
+    pub msaa: MsaaArg,
+    /// Edge length of the grid cells the chunked methods split the lines into, in world units.
+    /// Every non-empty cell becomes a mesh that is frustum culled on its own.
+    #[arg(long, default_value_t = 0.5, value_parser = parse_positive)]
+    pub chunk_size: f32,
+    /// Path the camera follows during the benchmark. Captures use where it starts. Use
+    /// `fly-through` to benchmark the culling of the chunked methods, which only pays off when
+    /// part of the lines is out of view.
+    #[arg(long, value_enum, default_value_t = CameraPathArg::Orbit)]
+    pub camera_path: CameraPathArg,
+    /// Distance of the camera from `--start` for the `orbit` and `static` camera paths. Below
+    /// `--radius` the camera moves through the lines, so a large part of them is behind it.
+    #[arg(long, default_value_t = 3.5, value_parser = parse_positive)]
+    pub orbit_radius: f32,
+    /// JSON file for `--camera-path keyframes`, holding an array of
+    /// `{"time": 0.0, "position": [x, y, z], "look_at": [x, y, z]}` objects.
+    #[arg(long, required_if_eq("camera_path", "keyframes"))]
+    pub camera_keyframes: Option<PathBuf>,
+    /// Let bevy_vector_shapes anti-alias its lines in the fragment shader.
+    #[arg(long)]
+    pub vector_shapes_laa: bool,
+    /// Percentage of the lines the dynamic methods move every frame.
+    #[arg(long, default_value_t = 1.0, value_parser = parse_percent)]
+    pub mutate_percent: f32,
+    /// Flag runs whose frame time coefficient of variation is above this percentage as unstable.
+    #[arg(long, default_value_t = 10.0)]
+    pub unstable_cv: f32,
+    /// Also record the main world CPU time, render world prepare time and GPU time of the main
+    /// 3D passes. GPU time needs an adapter with timestamp query support, such as Vulkan or DX12.
+    #[arg(long)]
+    pub gpu_timing: bool,
+    /// Keep logging enabled when benchmarking automatically.
+    #[arg(long)]
+    pub verbose: bool,
+    /// Save the benchmark results to this file. Files ending in `.csv` are saved as CSV, anything else as JSON.
+    #[arg(long)]
+    pub output: Option<PathBuf>,
+}
+
+impl BenchOptions {
+    /// Arguments that reproduce these options when passed to a child process.
+    /// `output` is left out since each child writes its own results.
+    pub fn to_args(&self) -> Vec<String> {
+        let mut args = vec![
+            "--line-count".to_string(),
+            self.line_count.to_string(),
+            "--resolution".to_string(),
+            self.resolution.to_string(),
+            "--present-mode".to_string(),
+            self.present_mode.name(),
+            "--frame-budget".to_string(),
+            self.frame_budget.to_string(),
+            "--warm-up".to_string(),
+            self.warm_up.to_string(),
+            "--duration".to_string(),
+            self.duration.to_string(),
+            "--source".to_string(),
+            self.source.name(),
+            "--seed".to_string(),
+            self.seed.to_string(),
+            "--start".to_string(),
+            format!("{},{},{}", self.start.x, self.start.y, self.start.z),
+            "--radius".to_string(),
+            self.radius.to_string(),
+            "--step-length".to_string(),
+            self.step_length.to_string(),
+            "--bounds".to_string(),
+            self.bounds
+                .to_possible_value()
+                .unwrap()
+                .get_name()
+                .to_string(),
+            "--line-width".to_string(),
+            self.line_width.to_string(),
+            "--line-pattern".to_string(),
+            self.line_pattern.name(),
+            "--pattern-units".to_string(),
+            self.pattern_units.name(),
+            "--pattern-scale".to_string(),
+            self.pattern_scale.to_string(),
+            "--msaa".to_string(),
+            self.msaa.name(),
+            "--chunk-size".to_string(),
+            self.chunk_size.to_string(),
+            "--camera-path".to_string(),
+            self.camera_path.name(),
+            "--orbit-radius".to_string(),
+            self.orbit_radius.to_string(),
+            "--mutate-percent".to_string(),
+            self.mutate_percent.to_string(),
+            "--unstable-cv".to_string(),
+            self.unstable_cv.to_string(),
+        ];
+        if self.auto_count {
+            args.push("--auto-count".to_string());
+        }
+        if let Some(memory_limit) = self.memory_limit {
+            args.push("--memory-limit".to_string());
+            args.push(memory_limit.to_string());
+        }
+        if self.headless {
+            args.push("--headless".to_string());
+        }
+        if let Some(camera_keyframes) = &self.camera_keyframes {
+            args.push("--camera-keyframes".to_string());
+            args.push(camera_keyframes.display().to_string());
+        }
+        if self.vector_shapes_laa {
+            args.push("--vector-shapes-laa".to_string());
+        }
+        if self.gpu_timing {
+            args.push("--gpu-timing".to_string());
+        }
+        if self.verbose {
+            args.push("--verbose".to_string());
+        }
+        args
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Resolution {
+    pub width: u32,
+    pub height: u32,
+}
+
+impl Resolution {
+    pub const fn new(width: u32, height: u32) -> Self {
+        Self { width, height }
+    }
+}
+
+impl fmt::Display for Resolution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}x{}", self.width, self.height)
+    }
+}
+
+impl FromStr for Resolution {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (width, height) = s
+            .split_once('x')
+            .ok_or_else(|| format!("expected WIDTHxHEIGHT, got `{s}`"))?;
+        let parse = |v: &str| {
+            v.trim()
+                .parse::<u32>()
+                .ok()
+                .filter(|v| *v > 0)
+                .ok_or_else(|| format!("invalid resolution `{s}`"))
+        };
+        Ok(Self::new(parse(width)?, parse(height)?))
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PresentModeArg {
+    Immediate,
+    Mailbox,
+    Fifo,
+    AutoVsync,
+    AutoNoVsync,
+}
+
+impl PresentModeArg {
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+impl From<PresentModeArg> for PresentMode {
+    fn from(mode: PresentModeArg) -> Self {
+        match mode {
+            PresentModeArg::Immediate => PresentMode::Immediate,
+            PresentModeArg::Mailbox => PresentMode::Mailbox,
+            PresentModeArg::Fifo => PresentMode::Fifo,
+            PresentModeArg::AutoVsync => PresentMode::AutoVsync,
+            PresentModeArg::AutoNoVsync => PresentMode::AutoNoVsync,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LinePatternArg {
+    Dash,
+    Dot,
+    DashDot,
+}
+
+impl LinePatternArg {
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+impl From<LinePatternArg> for LinePattern {
+    fn from(pattern: LinePatternArg) -> Self {
+        match pattern {
+            LinePatternArg::Dash => LinePattern::Dash,
+            LinePatternArg::Dot => LinePattern::Dot,
+            LinePatternArg::DashDot => LinePattern::DashDot,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum PatternUnitsArg {
+    Screen,
+    World,
+}
+
+impl PatternUnitsArg {
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+impl From<PatternUnitsArg> for PatternUnits {
+    fn from(units: PatternUnitsArg) -> Self {
+        match units {
+            PatternUnitsArg::Screen => PatternUnits::Screen,
+            PatternUnitsArg::World => PatternUnits::World,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MsaaArg {
+    #[value(name = "1")]
+    Off,
+    #[value(name = "2")]
+    Sample2,
+    #[value(name = "4")]
+    Sample4,
+    #[value(name = "8")]
+    Sample8,
+}
+
+impl MsaaArg {
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+impl From<MsaaArg> for Msaa {
+    fn from(msaa: MsaaArg) -> Self {
+        match msaa {
+            MsaaArg::Off => Msaa::Off,
+            MsaaArg::Sample2 => Msaa::Sample2,
+            MsaaArg::Sample4 => Msaa::Sample4,
+            MsaaArg::Sample8 => Msaa::Sample8,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CameraPathArg {
+    /// Circle around the origin at `--orbit-radius`, looking at it.
+    Orbit,
+    /// Loop through the middle of the lines, looking ahead.
+    FlyThrough,
+    /// Circle just outside the lines, so most of them are off screen.
+    CloseUp,
+    /// Stay where the orbit starts.
+    Static,
+    /// Follow the keyframes in `--camera-keyframes`.
+    Keyframes,
+}
+
+impl CameraPathArg {
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum BoundsArg {
+    Box,
+    Sphere,
+}
+
+impl From<BoundsArg> for Bounds {
+    fn from(bounds: BoundsArg) -> Self {
+        match bounds {
+            BoundsArg::Box => Bounds::Box,
+            BoundsArg::Sphere => Bounds::Sphere,
+        }
+    }
+}
+
+#[derive(ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LineSourceArg {
+    /// Short connected lines from a random walk.
+    Walk,
+    /// Lines between two random points, spanning much of the screen.
+    Long,
+    /// Edges of a wireframe grid.
+    Grid,
+    /// Lines radiating out from a few random centers.
+    Radial,
+    /// Short lines at random positions that don't share endpoints.
+    Disconnected,
+    /// Short lines packed into a small region around `--start`.
+    Clustered,
+}
+
+impl LineSourceArg {
+    pub fn name(&self) -> String {
+        self.to_possible_value().unwrap().get_name().to_string()
+    }
+}
+
+impl From<LineSourceArg> for LineSourceKind {
+    fn from(source: LineSourceArg) -> Self {
+        match source {
+            LineSourceArg::Walk => LineSourceKind::Walk,
+            LineSourceArg::Long => LineSourceKind::Long,
+            LineSourceArg::Grid => LineSourceKind::Grid,
+            LineSourceArg::Radial => LineSourceKind::Radial,
+            LineSourceArg::Disconnected => LineSourceKind::Disconnected,
+            LineSourceArg::Clustered => LineSourceKind::Clustered,
+        }
+    }
+}
@@ -0,0 +1,117 @@
+//! Compares benchmark results against a saved baseline.
+
+use crate::report::BenchReport;
+
+/// Prints the lines/ms change of every method in `current` relative to `baseline`.
+/// Returns the names of the methods that got slower by more than `max_regression` percent,
+/// and of the methods in `baseline` that have no results in `current`.
+pub fn compare_reports(
+    baseline: &BenchReport,
+    current: &BenchReport,
+    max_regression: f32,
+) -> Vec<String> {
+    let mut failures = Vec::new();
+    println!(
+        "{:<45} {:>14} {:>14} {:>9}",
+        "method", "baseline", "current", "change"
+    );
+    for result in &current.results {
+        let Some(base) = baseline.get(&result.method) else {
+            println!(
+                "{:<45} {:>14} {:>13.1}k {:>9}",
+                result.method,
+                "-",
+                result.lines_per_ms / 1000.0,
+                "new"
+            );
+            continue;
+        };
+        let change = (result.lines_per_ms - base.lines_per_ms) / base.lines_per_ms * 100.0;
+        let regressed = change < -max_regression;
+        let mut notes = Vec::new();
+        if regressed {
+            notes.push("REGRESSION".to_string());
+        }
+        if base.line_count != result.line_count {
+            notes.push(format!(
+                "line count {} -> {}",
+                base.line_count, result.line_count
+            ));
+        }
+        if result.unstable || base.unstable {
+            notes.push("unstable".to_string());
+        }
+        println!(
+            "{:<45} {:>13.1}k {:>13.1}k {:>+8.1}% {}",
+            result.method,
+            base.lines_per_ms / 1000.0,
+            result.lines_per_ms / 1000.0,
+            change,
+            notes.join(", ")
+        );
+        if regressed {
+            failures.push(result.method.clone());
+        }
+    }
+    for base in &baseline.results {
+        if current.get(&base.method).is_none() {
+            println!(
+                "{:<45} {:>13.1}k {:>14} {:>9} MISSING",
+                base.method,
+                base.lines_per_ms / 1000.0,
+                "-",
+                "-"
+            );
+            failures.push(base.method.clone());
+        }
+    }
+    failures
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::report::BenchResult;
+
+    fn report(results: &[(&str, f32)]) -> BenchReport {
+        BenchReport {
+            results: results
+                .iter()
+                .map(|&(method, lines_per_ms)| BenchResult {
+                    method: method.to_string(),
+                    line_count: 100_000,
+                    lines_per_ms,
+                    ..Default::default()
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn regressions_past_the_threshold_fail() {
+        let baseline = report(&[("a", 1000.0), ("b", 1000.0), ("c", 1000.0)]);
+        let current = report(&[("a", 960.0), ("b", 940.0), ("c", 1500.0)]);
+        assert_eq!(compare_reports(&baseline, &current, 5.0), ["b"]);
+    }
+
+    #[test]
+    fn regressions_at_the_threshold_pass() {
+        let baseline = report(&[("a", 1000.0)]);
+        let current = report(&[("a", 950.0)]);
+        assert!(compare_reports(&baseline, &current, 5.0).is_empty());
+    }
+
+    #[test]
+    fn baseline_methods_without_results_fail() {
+        let baseline = report(&[("a", 1000.0), ("b", 1000.0)]);
+        let current = report(&[("a", 1000.0)]);
+        assert_eq!(compare_reports(&baseline, &current, 5.0), ["b"]);
+    }
+
+    #[test]
+    fn methods_only_in_the_current_results_pass() {
+        let baseline = report(&[("a", 1000.0)]);
+        let current = report(&[("a", 1000.0), ("new", 10.0)]);
+        assert!(compare_reports(&baseline, &current, 5.0).is_empty());
+    }
+}
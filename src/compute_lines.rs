@@ -0,0 +1,442 @@
+//! Rasterizes lines in a compute shader instead of with the hardware line rasterizer. Each
+//! invocation steps along one line and keeps the closest depth of every pixel it touches in a
+//! buffer with atomic writes, which is then blitted onto the camera target.
+//!
+//! The pixels are one `u32` each in a storage buffer rather than a storage texture, since
+//! atomics on storage textures aren't portable, so the GPU bytes per line depend on the
+//! resolution. Line buffers are dispatched in slices like in [`crate::storage_lines`]. The lines
+//! are 1 pixel wide with both ends drawn and aren't anti-aliased, even with `--msaa`, so their
+//! coverage differs somewhat from `bevy_lines_example_retained`. `--gpu-timing` reports the
+//! rasterize and blit passes separately. Needs compute shaders, which WebGL2 doesn't have.
+
+use std::collections::HashMap;
+
+use bevy::{
+    core_pipeline::{
+        core_3d::{
+            graph::{Core3d, Node3d},
+            CORE_3D_DEPTH_FORMAT,
+        },
+        FullscreenShader,
+    },
+    ecs::query::QueryItem,
+    image::BevyDefault,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        diagnostic::RecordDiagnostics,
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::RenderAssets,
+        render_graph::{
+            NodeRunError, RenderGraphContext, RenderGraphExt, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            binding_types::{storage_buffer_read_only_sized, storage_buffer_sized, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice},
+        view::{
+            ExtractedView, ViewDepthTexture, ViewTarget, ViewUniform, ViewUniformOffset,
+            ViewUniforms,
+        },
+        Render, RenderApp, RenderStartup, RenderSystems,
+    },
+    shader::Shader,
+};
+
+use crate::{
+    methods::{MethodKind, MethodRegistry},
+    storage_lines::{GpuLineBuffer, LineBuffer},
+    LineGenSettings, RetainedLines, UpdateCountEvent,
+};
+
+/// Must match `@workgroup_size` in the shader.
+const WORKGROUP_SIZE: u32 = 64;
+
+/// Most workgroups a dispatch can have in one dimension on every backend.
+const MAX_WORKGROUPS_PER_DIMENSION: u32 = 65_535;
+
+const SHADER_PATH: &str = "shaders/compute_lines.wgsl";
+
+pub fn register_methods(registry: &mut MethodRegistry) {
+    registry
+        .register(
+            "compute_rasterizer_retained",
+            MethodKind::Retained,
+            "Lines in one storage buffer, rasterized by a compute shader and blitted to the camera",
+            compute_rasterizer_retained,
+        )
+        .with_max_line_count(GpuLineBuffer::max_lines);
+}
+
+pub struct ComputeLinesPlugin;
+
+impl Plugin for ComputeLinesPlugin {
+    fn build(&self, app: &mut App) {
+        // `LineBuffer` is set up by `StorageLinesPlugin`
+        app.add_plugins(ExtractComponentPlugin::<ComputeLines>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedRenderPipelines<ComputeLinesPipeline>>()
+            .init_resource::<ViewPixelBuffers>()
+            .add_systems(RenderStartup, init_compute_lines_pipeline)
+            .add_systems(
+                Render,
+                (
+                    prepare_compute_lines_pipelines.in_set(RenderSystems::Prepare),
+                    prepare_view_pixel_buffers.in_set(RenderSystems::PrepareResources),
+                    prepare_compute_lines_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<ComputeLinesNode>>(Core3d, ComputeLinesLabel)
+            .add_render_graph_edges(
+                Core3d,
+                (
+                    Node3d::MainOpaquePass,
+                    ComputeLinesLabel,
+                    Node3d::MainTransmissivePass,
+                ),
+            );
+    }
+}
+
+/// Rasterizes the lines of a [`LineBuffer`] in world space in a compute shader.
+#[derive(Component, Clone, ExtractComponent)]
+pub struct ComputeLines(pub Handle<LineBuffer>);
+
+#[derive(Resource)]
+struct ComputeLinesPipeline {
+    view_layout: BindGroupLayoutDescriptor,
+    rasterize_layout: BindGroupLayoutDescriptor,
+    blit_layout: BindGroupLayoutDescriptor,
+    rasterize_pipeline: CachedComputePipelineId,
+    fullscreen_shader: FullscreenShader,
+    shader: Handle<Shader>,
+}
+
+fn init_compute_lines_pipeline(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    fullscreen_shader: Res<FullscreenShader>,
+    pipeline_cache: Res<PipelineCache>,
+) {
+    let view_layout = BindGroupLayoutDescriptor::new(
+        "compute_lines_view_layout",
+        &BindGroupLayoutEntries::single(
+            ShaderStages::COMPUTE | ShaderStages::FRAGMENT,
+            uniform_buffer::<ViewUniform>(true),
+        ),
+    );
+    let rasterize_layout = BindGroupLayoutDescriptor::new(
+        "compute_lines_rasterize_layout",
+        &BindGroupLayoutEntries::sequential(
+            ShaderStages::COMPUTE,
+            (
+                storage_buffer_read_only_sized(false, None),
+                storage_buffer_sized(false, None),
+            ),
+        ),
+    );
+    let blit_layout = BindGroupLayoutDescriptor::new(
+        "compute_lines_blit_layout",
+        &BindGroupLayoutEntries::single(
+            ShaderStages::FRAGMENT,
+            storage_buffer_read_only_sized(false, None),
+        ),
+    );
+    let shader = asset_server.load(SHADER_PATH);
+    let rasterize_pipeline = pipeline_cache.queue_compute_pipeline(ComputePipelineDescriptor {
+        label: Some("compute_lines_rasterize_pipeline".into()),
+        layout: vec![view_layout.clone(), rasterize_layout.clone()],
+        shader: shader.clone(),
+        entry_point: Some("rasterize".into()),
+        ..default()
+    });
+    commands.insert_resource(ComputeLinesPipeline {
+        view_layout,
+        rasterize_layout,
+        blit_layout,
+        rasterize_pipeline,
+        fullscreen_shader: fullscreen_shader.clone(),
+        shader,
+    });
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct BlitPipelineKey {
+    hdr: bool,
+    samples: u32,
+}
+
+impl SpecializedRenderPipeline for ComputeLinesPipeline {
+    type Key = BlitPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("compute_lines_blit_pipeline".into()),
+            layout: vec![self.view_layout.clone(), self.blit_layout.clone()],
+            vertex: self.fullscreen_shader.to_vertex_state(),
+            // The blit writes the depth of the lines, so they're depth tested against meshes
+            depth_stencil: Some(DepthStencilState {
+                format: CORE_3D_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: key.samples,
+                ..default()
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                shader_defs: vec!["BLIT".into()],
+                entry_point: Some("blit".into()),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+            }),
+            ..default()
+        }
+    }
+}
+
+#[derive(Component)]
+struct BlitPipelineId(CachedRenderPipelineId);
+
+fn prepare_compute_lines_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<ComputeLinesPipeline>>,
+    pipeline: Res<ComputeLinesPipeline>,
+    views: Query<(Entity, &ExtractedView, &Msaa)>,
+) {
+    for (entity, view, msaa) in &views {
+        let pipeline_id = pipelines.specialize(
+            &pipeline_cache,
+            &pipeline,
+            BlitPipelineKey {
+                hdr: view.hdr,
+                samples: msaa.samples(),
+            },
+        );
+        commands.entity(entity).insert(BlitPipelineId(pipeline_id));
+    }
+}
+
+/// The closest depth of the lines in every pixel of a view, or 0 where there are none.
+struct PixelBuffer {
+    buffer: Buffer,
+    size: UVec2,
+}
+
+/// Pixel buffers of the views, kept between frames until their size changes.
+#[derive(Resource, Default)]
+struct ViewPixelBuffers(HashMap<Entity, PixelBuffer>);
+
+fn prepare_view_pixel_buffers(
+    mut pixel_buffers: ResMut<ViewPixelBuffers>,
+    render_device: Res<RenderDevice>,
+    views: Query<(Entity, &ExtractedView)>,
+    compute_lines: Query<(), With<ComputeLines>>,
+) {
+    // Only allocated while there are lines to draw
+    if compute_lines.is_empty() {
+        pixel_buffers.0.clear();
+        return;
+    }
+    pixel_buffers.0.retain(|entity, _| views.contains(*entity));
+    for (entity, view) in &views {
+        let size = view.viewport.zw();
+        if pixel_buffers
+            .0
+            .get(&entity)
+            .is_some_and(|pixels| pixels.size == size)
+        {
+            continue;
+        }
+        let buffer = render_device.create_buffer(&BufferDescriptor {
+            label: Some("compute_lines_pixel_buffer"),
+            size: (size.x * size.y) as u64 * size_of::<u32>() as u64,
+            usage: BufferUsages::STORAGE | BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        pixel_buffers.0.insert(entity, PixelBuffer { buffer, size });
+    }
+}
+
+#[derive(Component)]
+struct ComputeLinesBindGroups {
+    view: BindGroup,
+    pixels: Buffer,
+    blit: BindGroup,
+    /// One per slice of each line buffer, with its line count.
+    rasterize: Vec<(BindGroup, u32)>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_compute_lines_bind_groups(
+    mut commands: Commands,
+    pipeline: Res<ComputeLinesPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    view_uniforms: Res<ViewUniforms>,
+    render_device: Res<RenderDevice>,
+    pixel_buffers: Res<ViewPixelBuffers>,
+    line_buffers: Res<RenderAssets<GpuLineBuffer>>,
+    compute_lines: Query<&ComputeLines>,
+    stale_bind_groups: Query<Entity, With<ComputeLinesBindGroups>>,
+) {
+    // Buffers can be bigger than a storage binding may be, so they're dispatched a slice at a time
+    let max_lines = GpuLineBuffer::max_lines_per_binding(&render_device.limits());
+    let line_slices = compute_lines
+        .iter()
+        .filter_map(|lines| line_buffers.get(&lines.0))
+        .flat_map(|line_buffer| line_buffer.bindings(max_lines))
+        .collect::<Vec<_>>();
+    let view_uniforms = view_uniforms.uniforms.binding();
+    let Some(view_uniforms) = view_uniforms.filter(|_| !line_slices.is_empty()) else {
+        // The views are kept from frame to frame, so the node would keep drawing the old lines
+        for entity in &stale_bind_groups {
+            commands.entity(entity).remove::<ComputeLinesBindGroups>();
+        }
+        return;
+    };
+    let view = render_device.create_bind_group(
+        "compute_lines_view_bind_group",
+        &pipeline_cache.get_bind_group_layout(&pipeline.view_layout),
+        &BindGroupEntries::single(view_uniforms),
+    );
+    for (&entity, pixels) in &pixel_buffers.0 {
+        let blit = render_device.create_bind_group(
+            "compute_lines_blit_bind_group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.blit_layout),
+            &BindGroupEntries::single(pixels.buffer.as_entire_binding()),
+        );
+        let rasterize = line_slices
+            .iter()
+            .map(|(binding, line_count)| {
+                let bind_group = render_device.create_bind_group(
+                    "compute_lines_rasterize_bind_group",
+                    &pipeline_cache.get_bind_group_layout(&pipeline.rasterize_layout),
+                    &BindGroupEntries::sequential((
+                        binding.clone(),
+                        pixels.buffer.as_entire_binding(),
+                    )),
+                );
+                (bind_group, *line_count)
+            })
+            .collect();
+        commands.entity(entity).insert(ComputeLinesBindGroups {
+            view: view.clone(),
+            pixels: pixels.buffer.clone(),
+            blit,
+            rasterize,
+        });
+    }
+}
+
+/// Workgroups to dispatch for `line_count` lines, spilling into the second dimension when
+/// there are too many for the first.
+fn workgroups(line_count: u32) -> (u32, u32) {
+    let workgroups = line_count.div_ceil(WORKGROUP_SIZE);
+    let x = workgroups.min(MAX_WORKGROUPS_PER_DIMENSION);
+    (x, workgroups.div_ceil(x))
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct ComputeLinesLabel;
+
+#[derive(Default)]
+struct ComputeLinesNode;
+
+impl ViewNode for ComputeLinesNode {
+    type ViewQuery = (
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        &'static ViewDepthTexture,
+        &'static ViewUniformOffset,
+        &'static BlitPipelineId,
+        &'static ComputeLinesBindGroups,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (camera, target, depth, view_uniform_offset, blit_pipeline_id, bind_groups): QueryItem<
+            Self::ViewQuery,
+        >,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let compute_lines_pipeline = world.resource::<ComputeLinesPipeline>();
+        let pipeline_cache = world.resource::<PipelineCache>();
+        let (Some(rasterize_pipeline), Some(blit_pipeline)) = (
+            pipeline_cache.get_compute_pipeline(compute_lines_pipeline.rasterize_pipeline),
+            pipeline_cache.get_render_pipeline(blit_pipeline_id.0),
+        ) else {
+            return Ok(());
+        };
+
+        let diagnostics = render_context.diagnostic_recorder();
+        let command_encoder = render_context.command_encoder();
+        command_encoder.clear_buffer(&bind_groups.pixels, 0, None);
+        {
+            let mut compute_pass = command_encoder.begin_compute_pass(&ComputePassDescriptor {
+                label: Some("compute_lines_rasterize"),
+                timestamp_writes: None,
+            });
+            let pass_span = diagnostics.pass_span(&mut compute_pass, "compute_lines_rasterize");
+            compute_pass.set_pipeline(rasterize_pipeline);
+            compute_pass.set_bind_group(0, &bind_groups.view, &[view_uniform_offset.offset]);
+            for (bind_group, line_count) in &bind_groups.rasterize {
+                let (x, y) = workgroups(*line_count);
+                compute_pass.set_bind_group(1, bind_group, &[]);
+                compute_pass.dispatch_workgroups(x, y, 1);
+            }
+            pass_span.end(&mut compute_pass);
+        }
+
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("compute_lines_blit"),
+            color_attachments: &[Some(target.get_color_attachment())],
+            depth_stencil_attachment: Some(depth.get_attachment(StoreOp::Store)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        let pass_span = diagnostics.pass_span(&mut render_pass, "compute_lines_blit");
+        if let Some(viewport) = camera.viewport.as_ref() {
+            render_pass.set_camera_viewport(viewport);
+        }
+        render_pass.set_render_pipeline(blit_pipeline);
+        render_pass.set_bind_group(0, &bind_groups.view, &[view_uniform_offset.offset]);
+        render_pass.set_bind_group(1, &bind_groups.blit, &[]);
+        render_pass.draw(0..3, 0..1);
+        pass_span.end(&mut render_pass);
+        Ok(())
+    }
+}
+
+pub fn compute_rasterizer_retained(
+    mut commands: Commands,
+    mut line_buffers: ResMut<Assets<LineBuffer>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = (0..count.0).map(|_| line_gen.next_line()).collect();
+    commands
+        .spawn(ComputeLines(line_buffers.add(LineBuffer { lines })))
+        .insert(RetainedLines);
+}
@@ -0,0 +1,368 @@
+//! Retained scenes that move a percentage of their lines every frame, to measure the cost of
+//! updating and re-uploading retained data that the static scenes never pay. The moved lines
+//! cycle through all of the lines, `--mutate-percent` of them per frame.
+//!
+//! `bevy_lines_example_retained_dynamic` rewrites the moved lines in the mesh position
+//! attribute, `gizmos_retained_combined_dynamic` rebuilds the `GizmoAsset` and
+//! `bevy_polyline_retained_nan_dynamic` edits the moved `Polyline` vertices.
+//! `bevy_lines_example_retained_chunked_dynamic` only rewrites the chunks with moved lines.
+
+use std::{collections::HashMap, ops::Range};
+
+use bevy::{asset::RenderAssetUsages, mesh::VertexAttributeValues, prelude::*};
+use bevy_polyline::{
+    material::PolylineMaterialHandle,
+    polyline::PolylineHandle,
+    prelude::{Polyline, PolylineBundle, PolylineMaterial},
+};
+
+use crate::{
+    bevy_lines_example::{LineChunks, LineList, LineMaterial},
+    methods::{MethodKind, MethodRegistry},
+    ChunkSize, LineGenSettings, LineMutation, LineWidth, RetainedLines, UpdateCountEvent,
+};
+
+pub fn register_methods(registry: &mut MethodRegistry) {
+    registry
+        .register(
+            "bevy_lines_example_retained_dynamic",
+            MethodKind::Dynamic,
+            "LineList mesh with the moved lines rewritten in its position attribute",
+            (bevy_lines_example_retained_dynamic, mutate_line_list),
+        )
+        .register(
+            "bevy_lines_example_retained_chunked_dynamic",
+            MethodKind::Dynamic,
+            "LineList meshes per --chunk-size grid cell, rewriting only the chunks that moved",
+            (
+                bevy_lines_example_retained_chunked_dynamic,
+                mutate_line_chunks,
+            ),
+        )
+        .with_chunks()
+        .register(
+            "gizmos_retained_combined_dynamic",
+            MethodKind::Dynamic,
+            "One GizmoAsset containing every line, rebuilt when lines move",
+            (gizmos_retained_combined_dynamic, mutate_gizmo_asset),
+        )
+        .with_line_width()
+        .register(
+            "bevy_polyline_retained_nan_dynamic",
+            MethodKind::Dynamic,
+            "One Polyline with lines separated by NaN, editing the moved vertices",
+            (bevy_polyline_retained_nan_dynamic, mutate_polyline),
+        )
+        .with_line_width();
+}
+
+/// The lines of a dynamic scene, and which of them move next.
+#[derive(Component)]
+pub struct DynamicLines {
+    lines: Vec<(Vec3, Vec3)>,
+    /// Where the lines would be if they never moved.
+    origins: Vec<(Vec3, Vec3)>,
+    next: usize,
+}
+
+impl DynamicLines {
+    fn new(lines: Vec<(Vec3, Vec3)>) -> Self {
+        Self {
+            origins: lines.clone(),
+            lines,
+            next: 0,
+        }
+    }
+
+    /// Moves the next `percent` of the lines, wrapping around so every line moves in turn.
+    /// Returns the ranges of lines that moved. The second one is only used when wrapping around.
+    fn mutate(&mut self, percent: f32, time: f32) -> [Range<usize>; 2] {
+        let len = self.lines.len();
+        let count = ((len as f32 * percent / 100.0).round() as usize).min(len);
+        if count == 0 {
+            return [0..0, 0..0];
+        }
+        let start = self.next;
+        let end = start + count;
+        self.next = end % len;
+        let ranges = if end <= len {
+            [start..end, 0..0]
+        } else {
+            [start..len, 0..end - len]
+        };
+
+        // Bob the lines up and down around where they started
+        let offset = Vec3::Y * (time * 4.0).sin() * 0.02;
+        for range in &ranges {
+            for i in range.clone() {
+                let (a, b) = self.origins[i];
+                self.lines[i] = (a + offset, b + offset);
+            }
+        }
+        ranges
+    }
+}
+
+fn generate_lines(line_gen_settings: &LineGenSettings, count: u32) -> Vec<(Vec3, Vec3)> {
+    let mut line_gen = line_gen_settings.generator(count);
+    (0..count).map(|_| line_gen.next_line()).collect()
+}
+
+pub fn bevy_lines_example_retained_dynamic(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let lines = generate_lines(&line_gen_settings, count.0);
+    // Kept in the main world as well so the positions can be rewritten
+    let mesh = LineList {
+        lines: lines.clone(),
+        ..default()
+    }
+    .into_mesh(RenderAssetUsages::default());
+    commands
+        .spawn((
+            Mesh3d(meshes.add(mesh)),
+            Transform::from_xyz(0.0, 0.0, 0.0),
+            MeshMaterial3d(materials.add(LineMaterial {
+                color: LinearRgba::WHITE,
+            })),
+            DynamicLines::new(lines),
+        ))
+        .insert(RetainedLines);
+}
+
+pub fn mutate_line_list(
+    mut dynamic_lines: Query<(&Mesh3d, &mut DynamicLines)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mutation: Res<LineMutation>,
+    time: Res<Time>,
+) {
+    for (mesh, mut dynamic_lines) in &mut dynamic_lines {
+        let ranges = dynamic_lines.mutate(mutation.percent, time.elapsed_secs());
+        if ranges[0].is_empty() {
+            continue;
+        }
+        let Some(mesh) = meshes.get_mut(&mesh.0) else {
+            continue;
+        };
+        let Some(VertexAttributeValues::Float32x3(positions)) =
+            mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+        else {
+            continue;
+        };
+        for i in ranges.into_iter().flatten() {
+            let (a, b) = dynamic_lines.lines[i];
+            positions[i * 2] = a.to_array();
+            positions[i * 2 + 1] = b.to_array();
+        }
+    }
+}
+
+/// The chunk meshes of a chunked dynamic scene, and where every line ended up in them.
+#[derive(Component)]
+pub struct LineChunkMeshes {
+    meshes: Vec<Handle<Mesh>>,
+    /// Chunk of every line, and its index within the chunk.
+    slots: Vec<(u32, u32)>,
+}
+
+pub fn bevy_lines_example_retained_chunked_dynamic(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut materials: ResMut<Assets<LineMaterial>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    chunk_size: Res<ChunkSize>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let lines = generate_lines(&line_gen_settings, count.0);
+    let material = materials.add(LineMaterial {
+        color: LinearRgba::WHITE,
+    });
+    let mut chunk_meshes = LineChunkMeshes {
+        meshes: Vec::new(),
+        slots: vec![(0, 0); lines.len()],
+    };
+    // Lines stay in the chunk they started in. They move so little that the bounds Bevy
+    // recomputes for every changed mesh stay close to the grid cell.
+    for (chunk, line_indices) in LineChunks::from_lines(&lines, chunk_size.0)
+        .chunks
+        .into_iter()
+        .enumerate()
+    {
+        for (slot, &i) in line_indices.iter().enumerate() {
+            chunk_meshes.slots[i as usize] = (chunk as u32, slot as u32);
+        }
+        let mesh = LineList {
+            lines: line_indices.iter().map(|&i| lines[i as usize]).collect(),
+            ..default()
+        }
+        .into_mesh(RenderAssetUsages::default());
+        let mesh = meshes.add(mesh);
+        chunk_meshes.meshes.push(mesh.clone());
+        commands
+            .spawn((
+                Mesh3d(mesh),
+                Transform::from_xyz(0.0, 0.0, 0.0),
+                MeshMaterial3d(material.clone()),
+            ))
+            .insert(RetainedLines);
+    }
+    commands
+        .spawn((chunk_meshes, DynamicLines::new(lines)))
+        .insert(RetainedLines);
+}
+
+pub fn mutate_line_chunks(
+    mut dynamic_lines: Query<(&LineChunkMeshes, &mut DynamicLines)>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mutation: Res<LineMutation>,
+    time: Res<Time>,
+) {
+    for (chunk_meshes, mut dynamic_lines) in &mut dynamic_lines {
+        let ranges = dynamic_lines.mutate(mutation.percent, time.elapsed_secs());
+        if ranges[0].is_empty() {
+            continue;
+        }
+        // Only the chunks with moved lines are touched, and so re-uploaded
+        let mut dirty_chunks = HashMap::<u32, Vec<usize>>::new();
+        for i in ranges.into_iter().flatten() {
+            dirty_chunks
+                .entry(chunk_meshes.slots[i].0)
+                .or_default()
+                .push(i);
+        }
+        for (chunk, moved) in dirty_chunks {
+            let Some(mesh) = meshes.get_mut(&chunk_meshes.meshes[chunk as usize]) else {
+                continue;
+            };
+            let Some(VertexAttributeValues::Float32x3(positions)) =
+                mesh.attribute_mut(Mesh::ATTRIBUTE_POSITION)
+            else {
+                continue;
+            };
+            for i in moved {
+                let slot = chunk_meshes.slots[i].1 as usize;
+                let (a, b) = dynamic_lines.lines[i];
+                positions[slot * 2] = a.to_array();
+                positions[slot * 2 + 1] = b.to_array();
+            }
+        }
+    }
+}
+
+pub fn gizmos_retained_combined_dynamic(
+    mut commands: Commands,
+    mut gizmos: ResMut<Assets<GizmoAsset>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let lines = generate_lines(&line_gen_settings, count.0);
+    commands
+        .spawn((
+            Gizmo {
+                handle: gizmos.add(line_gizmo(&lines)),
+                line_config: GizmoLineConfig {
+                    width: line_width.0,
+                    ..default()
+                },
+                ..default()
+            },
+            DynamicLines::new(lines),
+        ))
+        .insert(RetainedLines);
+}
+
+fn line_gizmo(lines: &[(Vec3, Vec3)]) -> GizmoAsset {
+    let mut linegizmo = GizmoAsset::default();
+    for line in lines {
+        linegizmo.line(line.0, line.1, Color::WHITE);
+    }
+    linegizmo
+}
+
+pub fn mutate_gizmo_asset(
+    mut dynamic_lines: Query<(&Gizmo, &mut DynamicLines)>,
+    mut gizmos: ResMut<Assets<GizmoAsset>>,
+    mutation: Res<LineMutation>,
+    time: Res<Time>,
+) {
+    for (gizmo, mut dynamic_lines) in &mut dynamic_lines {
+        let ranges = dynamic_lines.mutate(mutation.percent, time.elapsed_secs());
+        if ranges[0].is_empty() {
+            continue;
+        }
+        // A GizmoAsset can only be appended to, so it has to be rebuilt from scratch
+        if let Some(asset) = gizmos.get_mut(&gizmo.handle) {
+            *asset = line_gizmo(&dynamic_lines.lines);
+        }
+    }
+}
+
+pub fn bevy_polyline_retained_nan_dynamic(
+    mut commands: Commands,
+    mut polyline_materials: ResMut<Assets<PolylineMaterial>>,
+    mut polylines: ResMut<Assets<Polyline>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let lines = generate_lines(&line_gen_settings, count.0);
+    // Draws a single polyline (instead of individual lines) and inserts a NaN in between lines to separate them.
+    let vertices = lines
+        .iter()
+        .flat_map(|line| [line.0, line.1, Vec3::splat(f32::NAN)])
+        .collect();
+    commands
+        .spawn((
+            PolylineBundle {
+                polyline: PolylineHandle(polylines.add(Polyline { vertices })),
+                material: PolylineMaterialHandle(polyline_materials.add(PolylineMaterial {
+                    width: line_width.0,
+                    color: LinearRgba::WHITE,
+                    perspective: false,
+                    ..default()
+                })),
+                ..default()
+            },
+            DynamicLines::new(lines),
+        ))
+        .insert(RetainedLines);
+}
+
+pub fn mutate_polyline(
+    mut dynamic_lines: Query<(&PolylineHandle, &mut DynamicLines)>,
+    mut polylines: ResMut<Assets<Polyline>>,
+    mutation: Res<LineMutation>,
+    time: Res<Time>,
+) {
+    for (polyline, mut dynamic_lines) in &mut dynamic_lines {
+        let ranges = dynamic_lines.mutate(mutation.percent, time.elapsed_secs());
+        if ranges[0].is_empty() {
+            continue;
+        }
+        let Some(polyline) = polylines.get_mut(&polyline.0) else {
+            continue;
+        };
+        for i in ranges.into_iter().flatten() {
+            let (a, b) = dynamic_lines.lines[i];
+            polyline.vertices[i * 3] = a;
+            polyline.vertices[i * 3 + 1] = b;
+        }
+    }
+}
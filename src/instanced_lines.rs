@@ -0,0 +1,413 @@
+//! Draws one unit quad per line with instancing, in a custom render graph node. The line
+//! endpoints come from a per-instance vertex buffer, and the vertex shader expands every quad
+//! to a screen-space width. Sits between the entity per line of `bevy_plane_3d_retained` and
+//! the single baked mesh of `bevy_plane_3d_retained_combined`. The instance buffer is the same
+//! [`LineBuffer`] as the storage buffer methods use, so the lines still take 24 bytes each.
+
+use std::collections::HashMap;
+
+use bevy::{
+    core_pipeline::core_3d::{
+        graph::{Core3d, Node3d},
+        CORE_3D_DEPTH_FORMAT,
+    },
+    ecs::query::QueryItem,
+    image::BevyDefault,
+    mesh::VertexBufferLayout,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        diagnostic::RecordDiagnostics,
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::RenderAssets,
+        render_graph::{
+            NodeRunError, RenderGraphContext, RenderGraphExt, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{binding_types::uniform_buffer, *},
+        renderer::{RenderContext, RenderDevice, RenderQueue},
+        view::{
+            ExtractedView, ViewDepthTexture, ViewTarget, ViewUniform, ViewUniformOffset,
+            ViewUniforms,
+        },
+        Render, RenderApp, RenderStartup, RenderSystems,
+    },
+    shader::Shader,
+};
+
+use crate::{
+    methods::{MethodKind, MethodRegistry},
+    storage_lines::{GpuLineBuffer, LineBuffer},
+    LineGenSettings, LineWidth, RetainedLines, UpdateCountEvent,
+};
+
+/// Along the line from 0 at the start to 1 at the end, and across it from -1 to 1.
+const QUAD_VERTICES: [[f32; 2]; 4] = [[0.0, 1.0], [0.0, -1.0], [1.0, 1.0], [1.0, -1.0]];
+const QUAD_INDICES: [u16; 6] = [0, 1, 2, 1, 3, 2];
+
+pub fn register_methods(registry: &mut MethodRegistry) {
+    registry
+        .register(
+            "instanced_quads_retained",
+            MethodKind::Retained,
+            "One unit quad instanced per line, expanded to the line width in the vertex shader",
+            instanced_quads_retained,
+        )
+        .with_line_width()
+        .with_max_line_count(GpuLineBuffer::max_lines);
+}
+
+pub struct InstancedLinesPlugin;
+
+impl Plugin for InstancedLinesPlugin {
+    fn build(&self, app: &mut App) {
+        // `LineBuffer` is set up by `StorageLinesPlugin`
+        app.add_plugins(ExtractComponentPlugin::<InstancedLines>::default());
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedRenderPipelines<InstancedLinesPipeline>>()
+            .init_resource::<InstancedLinesBindGroups>()
+            .add_systems(RenderStartup, init_instanced_lines_pipeline)
+            .add_systems(
+                Render,
+                (
+                    prepare_instanced_lines_pipelines.in_set(RenderSystems::Prepare),
+                    prepare_instanced_lines_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<InstancedLinesNode>>(
+                Core3d,
+                InstancedLinesLabel,
+            )
+            .add_render_graph_edges(
+                Core3d,
+                (
+                    Node3d::MainOpaquePass,
+                    InstancedLinesLabel,
+                    Node3d::MainTransmissivePass,
+                ),
+            );
+    }
+}
+
+/// Draws the lines of a [`LineBuffer`] in world space as instanced quads `width` pixels wide.
+#[derive(Component, Clone, ExtractComponent)]
+pub struct InstancedLines {
+    pub lines: Handle<LineBuffer>,
+    pub width: f32,
+}
+
+#[derive(ShaderType)]
+struct InstancedLinesUniform {
+    width: f32,
+}
+
+#[derive(Resource)]
+struct InstancedLinesPipeline {
+    view_layout: BindGroupLayoutDescriptor,
+    lines_layout: BindGroupLayoutDescriptor,
+    quad_vertices: Buffer,
+    quad_indices: Buffer,
+    shader: Handle<Shader>,
+}
+
+fn init_instanced_lines_pipeline(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    render_device: Res<RenderDevice>,
+) {
+    commands.insert_resource(InstancedLinesPipeline {
+        view_layout: BindGroupLayoutDescriptor::new(
+            "instanced_lines_view_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                uniform_buffer::<ViewUniform>(true),
+            ),
+        ),
+        lines_layout: BindGroupLayoutDescriptor::new(
+            "instanced_lines_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                uniform_buffer::<InstancedLinesUniform>(false),
+            ),
+        ),
+        quad_vertices: render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("instanced_lines_quad_vertices"),
+            contents: &QUAD_VERTICES
+                .as_flattened()
+                .iter()
+                .flat_map(|x| x.to_le_bytes())
+                .collect::<Vec<u8>>(),
+            usage: BufferUsages::VERTEX,
+        }),
+        quad_indices: render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("instanced_lines_quad_indices"),
+            contents: &QUAD_INDICES
+                .iter()
+                .flat_map(|i| i.to_le_bytes())
+                .collect::<Vec<u8>>(),
+            usage: BufferUsages::INDEX,
+        }),
+        shader: asset_server.load("shaders/instanced_lines.wgsl"),
+    });
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct InstancedLinesPipelineKey {
+    hdr: bool,
+    samples: u32,
+}
+
+impl SpecializedRenderPipeline for InstancedLinesPipeline {
+    type Key = InstancedLinesPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("instanced_lines_pipeline".into()),
+            layout: vec![self.view_layout.clone(), self.lines_layout.clone()],
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                buffers: vec![
+                    VertexBufferLayout {
+                        array_stride: size_of::<[f32; 2]>() as u64,
+                        step_mode: VertexStepMode::Vertex,
+                        attributes: vec![VertexAttribute {
+                            format: VertexFormat::Float32x2,
+                            offset: 0,
+                            shader_location: 0,
+                        }],
+                    },
+                    // The line buffer itself, one line per instance
+                    VertexBufferLayout {
+                        array_stride: LineBuffer::BYTES_PER_LINE as u64,
+                        step_mode: VertexStepMode::Instance,
+                        attributes: vec![
+                            VertexAttribute {
+                                format: VertexFormat::Float32x3,
+                                offset: 0,
+                                shader_location: 1,
+                            },
+                            VertexAttribute {
+                                format: VertexFormat::Float32x3,
+                                offset: VertexFormat::Float32x3.size(),
+                                shader_location: 2,
+                            },
+                        ],
+                    },
+                ],
+                ..default()
+            },
+            // Which way a quad faces depends on the direction of its line on screen
+            primitive: PrimitiveState {
+                cull_mode: None,
+                ..default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: CORE_3D_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: key.samples,
+                ..default()
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                ..default()
+            }),
+            ..default()
+        }
+    }
+}
+
+#[derive(Component)]
+struct InstancedLinesPipelineId(CachedRenderPipelineId);
+
+fn prepare_instanced_lines_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<InstancedLinesPipeline>>,
+    pipeline: Res<InstancedLinesPipeline>,
+    views: Query<(Entity, &ExtractedView, &Msaa)>,
+) {
+    for (entity, view, msaa) in &views {
+        let pipeline_id = pipelines.specialize(
+            &pipeline_cache,
+            &pipeline,
+            InstancedLinesPipelineKey {
+                hdr: view.hdr,
+                samples: msaa.samples(),
+            },
+        );
+        commands
+            .entity(entity)
+            .insert(InstancedLinesPipelineId(pipeline_id));
+    }
+}
+
+/// Bind group of the views, and the width bind group, instance buffer and line count of every
+/// line buffer to draw this frame.
+#[derive(Resource, Default)]
+struct InstancedLinesBindGroups {
+    view: Option<BindGroup>,
+    lines: Vec<(BindGroup, Buffer, u32)>,
+    /// Width bind group of every `InstancedLines` entity, kept until its width changes.
+    widths: HashMap<Entity, (f32, BindGroup)>,
+}
+
+#[allow(clippy::too_many_arguments)]
+fn prepare_instanced_lines_bind_groups(
+    mut bind_groups: ResMut<InstancedLinesBindGroups>,
+    pipeline: Res<InstancedLinesPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    view_uniforms: Res<ViewUniforms>,
+    render_device: Res<RenderDevice>,
+    render_queue: Res<RenderQueue>,
+    line_buffers: Res<RenderAssets<GpuLineBuffer>>,
+    instanced_lines: Query<(Entity, &InstancedLines)>,
+) {
+    bind_groups.view = view_uniforms.uniforms.binding().map(|view_uniforms| {
+        render_device.create_bind_group(
+            "instanced_lines_view_bind_group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.view_layout),
+            &BindGroupEntries::single(view_uniforms),
+        )
+    });
+    let bind_groups = bind_groups.into_inner();
+    bind_groups
+        .widths
+        .retain(|entity, _| instanced_lines.contains(*entity));
+    bind_groups.lines.clear();
+    for (entity, lines) in &instanced_lines {
+        let Some(line_buffer) = line_buffers.get(&lines.lines) else {
+            continue;
+        };
+        if line_buffer.vertex_count == 0 {
+            continue;
+        }
+        let width = bind_groups.widths.get(&entity);
+        let bind_group = match width {
+            Some((width, bind_group)) if *width == lines.width => bind_group.clone(),
+            _ => {
+                let mut uniform = UniformBuffer::from(InstancedLinesUniform { width: lines.width });
+                uniform.write_buffer(&render_device, &render_queue);
+                // The bind group keeps the uniform buffer alive
+                let bind_group = render_device.create_bind_group(
+                    "instanced_lines_bind_group",
+                    &pipeline_cache.get_bind_group_layout(&pipeline.lines_layout),
+                    &BindGroupEntries::single(uniform.binding().unwrap()),
+                );
+                bind_groups
+                    .widths
+                    .insert(entity, (lines.width, bind_group.clone()));
+                bind_group
+            }
+        };
+        bind_groups.lines.push((
+            bind_group,
+            line_buffer.buffer.clone(),
+            line_buffer.vertex_count / 2,
+        ));
+    }
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct InstancedLinesLabel;
+
+#[derive(Default)]
+struct InstancedLinesNode;
+
+impl ViewNode for InstancedLinesNode {
+    type ViewQuery = (
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        &'static ViewDepthTexture,
+        &'static ViewUniformOffset,
+        &'static InstancedLinesPipelineId,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (camera, target, depth, view_uniform_offset, pipeline_id): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let bind_groups = world.resource::<InstancedLinesBindGroups>();
+        let Some(view_bind_group) = &bind_groups.view else {
+            return Ok(());
+        };
+        if bind_groups.lines.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline_id.0)
+        else {
+            return Ok(());
+        };
+        let instanced_lines_pipeline = world.resource::<InstancedLinesPipeline>();
+
+        let diagnostics = render_context.diagnostic_recorder();
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("instanced_lines_pass"),
+            color_attachments: &[Some(target.get_color_attachment())],
+            depth_stencil_attachment: Some(depth.get_attachment(StoreOp::Store)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        let pass_span = diagnostics.pass_span(&mut render_pass, "instanced_lines_pass");
+        if let Some(viewport) = camera.viewport.as_ref() {
+            render_pass.set_camera_viewport(viewport);
+        }
+
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, view_bind_group, &[view_uniform_offset.offset]);
+        render_pass.set_vertex_buffer(0, instanced_lines_pipeline.quad_vertices.slice(..));
+        render_pass.set_index_buffer(
+            instanced_lines_pipeline.quad_indices.slice(..),
+            IndexFormat::Uint16,
+        );
+        for (bind_group, instances, line_count) in &bind_groups.lines {
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.set_vertex_buffer(1, instances.slice(..));
+            render_pass.draw_indexed(0..QUAD_INDICES.len() as u32, 0, 0..*line_count);
+        }
+
+        pass_span.end(&mut render_pass);
+        Ok(())
+    }
+}
+
+pub fn instanced_quads_retained(
+    mut commands: Commands,
+    mut line_buffers: ResMut<Assets<LineBuffer>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+    line_width: Res<LineWidth>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = (0..count.0).map(|_| line_gen.next_line()).collect();
+    commands
+        .spawn(InstancedLines {
+            lines: line_buffers.add(LineBuffer { lines }),
+            width: line_width.0,
+        })
+        .insert(RetainedLines);
+}
@@ -0,0 +1,240 @@
+//! Estimates how much memory the lines take, so the tuner can stop before running out and the
+//! benchmark can report the footprint of each method.
+//!
+//! The GPU estimate comes from the mesh, gizmo and polyline assets a method creates, plus the
+//! uniforms Bevy keeps for every mesh entity, which dominate methods that spawn an entity per
+//! line. Methods that use none of these, like bevy_vector_shapes, have no estimate. The tuner
+//! also watches the resident memory of the process grow.
+
+use std::{collections::HashMap, fs};
+
+use bevy::{
+    asset::RenderAssetUsages,
+    asset::UntypedAssetId,
+    ecs::system::SystemParam,
+    gizmos::GizmoAsset,
+    mesh::{Indices, Mesh},
+    pbr::{MeshInputUniform, MeshUniform},
+    prelude::*,
+    render::storage::ShaderStorageBuffer,
+};
+use bevy_polyline::polyline::Polyline;
+
+use crate::{compute_lines::ComputeLines, storage_lines::LineBuffer};
+
+pub struct MemoryPlugin;
+
+impl Plugin for MemoryPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MemoryUsage>()
+            .add_systems(PreStartup, record_baseline_rss)
+            .add_systems(
+                Last,
+                (
+                    track_asset_bytes::<Mesh>,
+                    track_asset_bytes::<GizmoAsset>,
+                    track_asset_bytes::<Polyline>,
+                    track_asset_bytes::<LineBuffer>,
+                    track_asset_bytes::<ShaderStorageBuffer>,
+                ),
+            );
+    }
+}
+
+/// CPU-side line data of the current scene and the memory used by the process before it
+/// was spawned.
+#[derive(Resource, Default)]
+pub struct MemoryUsage {
+    baseline_rss: Option<u64>,
+    assets: HashMap<UntypedAssetId, AssetMemory>,
+}
+
+struct AssetMemory {
+    bytes: u64,
+    main_world: bool,
+}
+
+impl MemoryUsage {
+    /// Bytes of mesh, gizmo, polyline, line buffer and storage buffer data uploaded to the GPU since the last `clear`.
+    pub fn uploaded_bytes(&self) -> u64 {
+        self.assets.values().map(|asset| asset.bytes).sum()
+    }
+
+    /// Bytes of the uploaded data that is also kept in the main world.
+    pub fn main_world_bytes(&self) -> u64 {
+        self.assets
+            .values()
+            .filter(|asset| asset.main_world)
+            .map(|asset| asset.bytes)
+            .sum()
+    }
+
+    /// Forget the tracked assets, for when the scene is about to be respawned.
+    pub fn clear(&mut self) {
+        self.assets.clear();
+    }
+
+    /// Highest line count expected to fit within `limit` bytes of resident memory, assuming
+    /// memory grows linearly with the line count. Takes the larger of the asset data and the
+    /// growth in resident memory since startup as the cost of the current `line_count`.
+    pub fn max_line_count(&self, line_count: u32, limit: u64) -> Option<u32> {
+        let baseline = self.baseline_rss?;
+        let rss_growth = process_rss_bytes()?.saturating_sub(baseline);
+        let bytes_per_line = self.uploaded_bytes().max(rss_growth) as f64 / line_count as f64;
+        if bytes_per_line <= 0.0 {
+            return None;
+        }
+        let max = limit.saturating_sub(baseline) as f64 / bytes_per_line;
+        Some(max.min(u32::MAX as f64) as u32)
+    }
+}
+
+/// GPU memory that grows with the entities of the scene rather than its assets.
+#[derive(SystemParam)]
+pub struct EntityGpuBytes<'w, 's> {
+    meshes: Query<'w, 's, (), With<Mesh3d>>,
+    compute_lines: Query<'w, 's, (), With<ComputeLines>>,
+    cameras: Query<'w, 's, &'static Camera>,
+}
+
+impl EntityGpuBytes<'_, '_> {
+    /// The per-instance input and output uniforms of every mesh entity, plus the `u32` per
+    /// pixel that `ComputeLines` rasterize into for each camera. Counts all the entities, so
+    /// it's too slow to call every frame.
+    pub fn bytes(&self) -> u64 {
+        let mesh_bytes = self.meshes.iter().count() as u64
+            * (size_of::<MeshUniform>() + size_of::<MeshInputUniform>()) as u64;
+        let pixel_bytes = if self.compute_lines.is_empty() {
+            0
+        } else {
+            self.cameras
+                .iter()
+                .filter_map(Camera::physical_target_size)
+                .map(|size| size.x as u64 * size.y as u64 * size_of::<u32>() as u64)
+                .sum()
+        };
+        mesh_bytes + pixel_bytes
+    }
+}
+
+/// Size of the line data of an asset in bytes.
+pub trait AssetBytes: Asset {
+    fn asset_bytes(&self) -> u64;
+
+    /// Whether the data stays in the main world after it's uploaded.
+    fn main_world(&self) -> bool {
+        true
+    }
+}
+
+impl AssetBytes for Mesh {
+    fn asset_bytes(&self) -> u64 {
+        let vertex_bytes = self
+            .attributes()
+            .map(|(_, values)| values.get_bytes().len() as u64)
+            .sum::<u64>();
+        let index_bytes = match self.indices() {
+            Some(Indices::U16(indices)) => indices.len() as u64 * 2,
+            Some(Indices::U32(indices)) => indices.len() as u64 * 4,
+            None => 0,
+        };
+        vertex_bytes + index_bytes
+    }
+
+    fn main_world(&self) -> bool {
+        self.asset_usage.contains(RenderAssetUsages::MAIN_WORLD)
+    }
+}
+
+impl AssetBytes for GizmoAsset {
+    fn asset_bytes(&self) -> u64 {
+        let buffer = self.buffer();
+        let positions = buffer.list_positions.len() + buffer.strip_positions.len();
+        let colors = buffer.list_colors.len() + buffer.strip_colors.len();
+        (positions * size_of::<Vec3>() + colors * size_of::<LinearRgba>()) as u64
+    }
+}
+
+impl AssetBytes for Polyline {
+    fn asset_bytes(&self) -> u64 {
+        (self.vertices.len() * size_of::<Vec3>()) as u64
+    }
+}
+
+impl AssetBytes for ShaderStorageBuffer {
+    fn asset_bytes(&self) -> u64 {
+        self.data
+            .as_ref()
+            .map_or(self.buffer_description.size, |data| data.len() as u64)
+    }
+
+    fn main_world(&self) -> bool {
+        self.asset_usage.contains(RenderAssetUsages::MAIN_WORLD)
+    }
+}
+
+impl AssetBytes for LineBuffer {
+    fn asset_bytes(&self) -> u64 {
+        (self.lines.len() * LineBuffer::BYTES_PER_LINE) as u64
+    }
+
+    fn main_world(&self) -> bool {
+        false
+    }
+}
+
+/// Runs after the renderer is initialized but before any lines are spawned.
+fn record_baseline_rss(mut memory: ResMut<MemoryUsage>) {
+    memory.baseline_rss = process_rss_bytes();
+}
+
+/// Records the size of assets as they're added. This runs before extraction, since assets
+/// that only live in the render world are removed from the main world once extracted.
+fn track_asset_bytes<A: AssetBytes>(
+    mut events: MessageReader<AssetEvent<A>>,
+    assets: Res<Assets<A>>,
+    mut memory: ResMut<MemoryUsage>,
+) {
+    for event in events.read() {
+        match event {
+            AssetEvent::Added { id } | AssetEvent::Modified { id } => {
+                if let Some(asset) = assets.get(*id) {
+                    memory.assets.insert(
+                        id.untyped(),
+                        AssetMemory {
+                            bytes: asset.asset_bytes(),
+                            main_world: asset.main_world(),
+                        },
+                    );
+                }
+            }
+            AssetEvent::Unused { id } => {
+                memory.assets.remove(&id.untyped());
+            }
+            _ => {}
+        }
+    }
+}
+
+/// Resident memory of this process, on Linux.
+pub fn process_rss_bytes() -> Option<u64> {
+    proc_kib("/proc/self/status", "VmRSS:").map(|kib| kib * 1024)
+}
+
+/// Default memory limit of the tuner, 80% of the physical memory, on Linux.
+pub fn default_memory_limit() -> Option<u64> {
+    proc_kib("/proc/meminfo", "MemTotal:").map(|kib| kib * 1024 / 5 * 4)
+}
+
+/// Reads a `<key> <value> kB` line from a file in `/proc`.
+fn proc_kib(path: &str, key: &str) -> Option<u64> {
+    fs::read_to_string(path)
+        .ok()?
+        .lines()
+        .find_map(|line| line.strip_prefix(key))?
+        .trim()
+        .trim_end_matches("kB")
+        .trim()
+        .parse()
+        .ok()
+}
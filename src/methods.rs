@@ -0,0 +1,138 @@
+//! Registry of the line rendering methods that can be run and benchmarked.
+
+use std::fmt;
+
+use bevy::{ecs::system::ScheduleSystem, prelude::*, render::settings::WgpuLimits};
+
+use crate::{basic_line_scenes, compute_lines, dynamic_scenes, instanced_lines, storage_lines};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MethodKind {
+    /// Builds its lines once when the line count changes.
+    Retained,
+    /// Rebuilds all of its lines every frame.
+    Immediate,
+    /// Builds its lines once and moves `--mutate-percent` of them every frame.
+    Dynamic,
+}
+
+impl fmt::Display for MethodKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.pad(match self {
+            MethodKind::Retained => "retained",
+            MethodKind::Immediate => "immediate",
+            MethodKind::Dynamic => "dynamic",
+        })
+    }
+}
+
+pub struct LineMethod {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub kind: MethodKind,
+    /// Most lines the method can draw on a device with the given limits, for methods that
+    /// keep all their lines in one buffer.
+    pub max_line_count: Option<fn(&WgpuLimits) -> u32>,
+    /// Draws lines `--line-width` pixels wide, so it gets the `LineWidth` resource.
+    pub line_width: bool,
+    /// Splits its lines into `--chunk-size` cells, so it gets the `ChunkSize` resource.
+    pub chunked: bool,
+    /// Leaves gaps along its lines for `--line-pattern`, so it covers fewer pixels than solid
+    /// lines do.
+    pub patterned: bool,
+    add_systems: Box<dyn Fn(&mut App) + Send + Sync>,
+}
+
+impl LineMethod {
+    /// Adds the systems that draw the lines for this method to the app.
+    pub fn add_to_app(&self, app: &mut App) {
+        (self.add_systems)(app);
+    }
+}
+
+/// All the methods, in the order they are benchmarked.
+pub struct MethodRegistry {
+    methods: Vec<LineMethod>,
+}
+
+impl Default for MethodRegistry {
+    fn default() -> Self {
+        let mut registry = Self {
+            methods: Vec::new(),
+        };
+        basic_line_scenes::register_methods(&mut registry);
+        dynamic_scenes::register_methods(&mut registry);
+        storage_lines::register_methods(&mut registry);
+        compute_lines::register_methods(&mut registry);
+        instanced_lines::register_methods(&mut registry);
+        registry
+    }
+}
+
+impl MethodRegistry {
+    pub fn register<M>(
+        &mut self,
+        name: &'static str,
+        kind: MethodKind,
+        description: &'static str,
+        systems: impl IntoScheduleConfigs<ScheduleSystem, M> + Clone + Send + Sync + 'static,
+    ) -> &mut Self {
+        assert!(
+            self.get(name).is_none(),
+            "method `{name}` is registered twice"
+        );
+        self.methods.push(LineMethod {
+            name,
+            description,
+            kind,
+            max_line_count: None,
+            line_width: false,
+            chunked: false,
+            patterned: false,
+            add_systems: Box::new(move |app| {
+                app.add_systems(Update, systems.clone());
+            }),
+        });
+        self
+    }
+
+    /// Limits the line count of the method registered last.
+    pub fn with_max_line_count(&mut self, max_line_count: fn(&WgpuLimits) -> u32) -> &mut Self {
+        self.last_registered().max_line_count = Some(max_line_count);
+        self
+    }
+
+    /// Marks the method registered last as drawing `--line-width` wide lines.
+    pub fn with_line_width(&mut self) -> &mut Self {
+        self.last_registered().line_width = true;
+        self
+    }
+
+    /// Marks the method registered last as splitting its lines into `--chunk-size` cells.
+    pub fn with_chunks(&mut self) -> &mut Self {
+        self.last_registered().chunked = true;
+        self
+    }
+
+    /// Marks the method registered last as drawing its lines with `--line-pattern`.
+    pub fn with_pattern(&mut self) -> &mut Self {
+        self.last_registered().patterned = true;
+        self
+    }
+
+    fn last_registered(&mut self) -> &mut LineMethod {
+        self.methods.last_mut().expect("no method registered yet")
+    }
+
+    pub fn get(&self, name: &str) -> Option<&LineMethod> {
+        self.methods.iter().find(|method| method.name == name)
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &LineMethod> {
+        self.methods.iter()
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.methods.iter().map(|method| method.name)
+    }
+}
@@ -0,0 +1,341 @@
+//! Benchmark results that can be saved as JSON or CSV and merged across runs.
+
+use std::{
+    fs, io,
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use serde::{Deserialize, Serialize};
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct BenchResult {
+    pub method: String,
+    pub line_count: u32,
+    pub frame_count: u32,
+    pub mean_frame_ms: f32,
+    pub median_frame_ms: f32,
+    pub p95_frame_ms: f32,
+    pub p99_frame_ms: f32,
+    #[serde(default)]
+    pub min_frame_ms: f32,
+    #[serde(default)]
+    pub max_frame_ms: f32,
+    #[serde(default)]
+    pub std_dev_frame_ms: f32,
+    /// Standard deviation of the frame times as a percentage of the mean.
+    #[serde(default)]
+    pub cv_percent: f32,
+    /// Set when `cv_percent` is above the threshold the run was made with.
+    #[serde(default)]
+    pub unstable: bool,
+    pub lines_per_ms: f32,
+    pub resolution: String,
+    pub present_mode: String,
+    /// Rendered into an offscreen image instead of a window.
+    #[serde(default)]
+    pub headless: bool,
+    pub git_revision: String,
+    /// Seeds of the random line walk, separated by spaces when averaged over several runs.
+    #[serde(default)]
+    pub seeds: String,
+    /// Distribution of the lines that were drawn.
+    #[serde(default)]
+    pub source: String,
+    /// Mean time spent in the main world schedule, recorded with `--gpu-timing`.
+    #[serde(default)]
+    pub cpu_frame_ms: Option<f32>,
+    /// Mean time the render world spent preparing before the render graph ran, recorded with `--gpu-timing`.
+    #[serde(default)]
+    pub render_prepare_ms: Option<f32>,
+    /// Mean GPU time of the main 3D passes, recorded with `--gpu-timing` when the adapter supports timestamp queries.
+    #[serde(default)]
+    pub gpu_pass_ms: Option<f32>,
+    /// Frame budget `line_count` was tuned to with `--auto-count`, making it the most lines
+    /// the method can draw within the budget.
+    #[serde(default)]
+    pub frame_budget_ms: Option<f32>,
+    /// Set when `--auto-count` stopped at the memory limit rather than the frame budget.
+    #[serde(default)]
+    pub memory_bound: bool,
+    /// Bytes of line data kept in the main world, such as meshes with `MAIN_WORLD` usage.
+    #[serde(default)]
+    pub cpu_asset_bytes: u64,
+    /// Estimated bytes of line data uploaded to the GPU per line, including the uniforms of
+    /// mesh entities and the pixel buffer of `compute_rasterizer_retained`. Missing for methods whose data isn't tracked, like `bevy_vector_shapes` instances.
+    #[serde(default)]
+    pub gpu_bytes_per_line: Option<f32>,
+    /// Entities in the main world, including the camera and window.
+    #[serde(default)]
+    pub entity_count: u32,
+    /// Percentage of the lines moved every frame by dynamic methods.
+    #[serde(default)]
+    pub mutate_percent: Option<f32>,
+    /// Width of the lines in pixels. Missing for methods that ignore `--line-width`.
+    #[serde(default)]
+    pub line_width: Option<f32>,
+    /// Samples per pixel of multisample anti-aliasing.
+    #[serde(default)]
+    pub msaa_samples: Option<u32>,
+    /// Edge length of the grid cells of the chunked methods. Missing for the other methods.
+    #[serde(default)]
+    pub chunk_size: Option<f32>,
+    /// Distance of the orbiting camera from the center of the lines.
+    #[serde(default)]
+    pub orbit_radius: Option<f32>,
+    /// Path the camera followed, as passed to `--camera-path`.
+    #[serde(default)]
+    pub camera_path: Option<String>,
+}
+
+impl BenchResult {
+    /// Averages several runs of the same method, such as with different seeds.
+    /// The percentiles are averaged per run, so they are only an approximation.
+    pub fn average(results: &[BenchResult]) -> Option<BenchResult> {
+        let first = results.first()?;
+        let n = results.len() as f32;
+        let avg = |f: fn(&BenchResult) -> f32| results.iter().map(f).sum::<f32>() / n;
+        // Only averaged when every run has the value
+        let avg_opt = |f: fn(&BenchResult) -> Option<f32>| {
+            results
+                .iter()
+                .map(f)
+                .sum::<Option<f32>>()
+                .map(|sum| sum / n)
+        };
+        // Byte counts can pass 2^24, past which f32 drops bytes
+        let avg_u64 = |f: fn(&BenchResult) -> u64| {
+            (results.iter().map(f).sum::<u64>() as f64 / results.len() as f64).round() as u64
+        };
+        Some(BenchResult {
+            method: first.method.clone(),
+            line_count: (results.iter().map(|r| r.line_count as f32).sum::<f32>() / n) as u32,
+            frame_count: results.iter().map(|r| r.frame_count).sum(),
+            mean_frame_ms: avg(|r| r.mean_frame_ms),
+            median_frame_ms: avg(|r| r.median_frame_ms),
+            p95_frame_ms: avg(|r| r.p95_frame_ms),
+            p99_frame_ms: avg(|r| r.p99_frame_ms),
+            min_frame_ms: results
+                .iter()
+                .map(|r| r.min_frame_ms)
+                .fold(f32::MAX, f32::min),
+            max_frame_ms: results.iter().map(|r| r.max_frame_ms).fold(0.0, f32::max),
+            std_dev_frame_ms: avg(|r| r.std_dev_frame_ms),
+            cv_percent: avg(|r| r.cv_percent),
+            unstable: results.iter().any(|r| r.unstable),
+            lines_per_ms: avg(|r| r.lines_per_ms),
+            resolution: first.resolution.clone(),
+            present_mode: first.present_mode.clone(),
+            headless: first.headless,
+            git_revision: first.git_revision.clone(),
+            seeds: results
+                .iter()
+                .map(|r| r.seeds.as_str())
+                .collect::<Vec<_>>()
+                .join(" "),
+            source: first.source.clone(),
+            cpu_frame_ms: avg_opt(|r| r.cpu_frame_ms),
+            render_prepare_ms: avg_opt(|r| r.render_prepare_ms),
+            gpu_pass_ms: avg_opt(|r| r.gpu_pass_ms),
+            frame_budget_ms: first.frame_budget_ms,
+            memory_bound: results.iter().any(|r| r.memory_bound),
+            cpu_asset_bytes: avg_u64(|r| r.cpu_asset_bytes),
+            gpu_bytes_per_line: avg_opt(|r| r.gpu_bytes_per_line),
+            entity_count: avg_u64(|r| r.entity_count as u64) as u32,
+            mutate_percent: first.mutate_percent,
+            line_width: first.line_width,
+            msaa_samples: first.msaa_samples,
+            chunk_size: first.chunk_size,
+            orbit_radius: first.orbit_radius,
+            camera_path: first.camera_path.clone(),
+        })
+    }
+}
+
+#[derive(Serialize, Deserialize, Default, Clone, Debug)]
+pub struct BenchReport {
+    pub results: Vec<BenchResult>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReportFormat {
+    Json,
+    Csv,
+}
+
+impl ReportFormat {
+    /// Picks CSV for `.csv` files and JSON for everything else.
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("csv") => ReportFormat::Csv,
+            _ => ReportFormat::Json,
+        }
+    }
+}
+
+impl BenchReport {
+    pub fn save(&self, path: &Path) -> io::Result<()> {
+        match ReportFormat::from_path(path) {
+            ReportFormat::Json => fs::write(path, serde_json::to_string_pretty(self)?),
+            ReportFormat::Csv => {
+                let mut writer = csv::Writer::from_path(path)?;
+                for result in &self.results {
+                    writer.serialize(result)?;
+                }
+                writer.flush()
+            }
+        }
+    }
+
+    pub fn load(path: &Path) -> io::Result<Self> {
+        match ReportFormat::from_path(path) {
+            ReportFormat::Json => Ok(serde_json::from_str(&fs::read_to_string(path)?)?),
+            ReportFormat::Csv => {
+                let results = csv::Reader::from_path(path)?
+                    .deserialize()
+                    .collect::<Result<_, _>>()?;
+                Ok(Self { results })
+            }
+        }
+    }
+
+    /// The first result for `method`, if there is one.
+    pub fn get(&self, method: &str) -> Option<&BenchResult> {
+        self.results.iter().find(|result| result.method == method)
+    }
+}
+
+/// Summary of the individual frame times of a benchmark run, in milliseconds.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct FrameStats {
+    pub mean: f32,
+    pub median: f32,
+    pub p95: f32,
+    pub p99: f32,
+    pub min: f32,
+    pub max: f32,
+    pub std_dev: f32,
+}
+
+impl FrameStats {
+    pub fn from_frame_times(frame_times_ms: &[f32]) -> Self {
+        if frame_times_ms.is_empty() {
+            return Self::default();
+        }
+        let mut sorted = frame_times_ms.to_vec();
+        sorted.sort_by(f32::total_cmp);
+        let mean = sorted.iter().sum::<f32>() / sorted.len() as f32;
+        let variance = sorted.iter().map(|t| (t - mean).powi(2)).sum::<f32>() / sorted.len() as f32;
+        Self {
+            mean,
+            median: percentile(&sorted, 50.0),
+            p95: percentile(&sorted, 95.0),
+            p99: percentile(&sorted, 99.0),
+            min: sorted[0],
+            max: sorted[sorted.len() - 1],
+            std_dev: variance.sqrt(),
+        }
+    }
+
+    /// Coefficient of variation, the standard deviation as a percentage of the mean.
+    pub fn cv_percent(&self) -> f32 {
+        if self.mean > 0.0 {
+            self.std_dev / self.mean * 100.0
+        } else {
+            0.0
+        }
+    }
+}
+
+/// Nearest-rank percentile of already sorted values.
+fn percentile(sorted: &[f32], p: f32) -> f32 {
+    let rank = ((p / 100.0) * sorted.len() as f32).ceil() as usize;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Formats a byte count with a binary unit, e.g. `1.5 MiB`.
+pub fn format_bytes(bytes: u64) -> String {
+    const UNITS: [&str; 4] = ["B", "KiB", "MiB", "GiB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1024.0 && unit < UNITS.len() - 1 {
+        value /= 1024.0;
+        unit += 1;
+    }
+    if unit == 0 {
+        format!("{bytes} B")
+    } else {
+        format!("{value:.1} {}", UNITS[unit])
+    }
+}
+
+/// Short hash of the checked out commit, or `unknown` when git isn't available.
+pub fn git_revision() -> String {
+    Command::new("git")
+        .args(["describe", "--always", "--dirty"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|rev| rev.trim().to_string())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+/// Where a benchmark child process writes its results before the parent merges them.
+pub fn child_output_path(method: &str) -> PathBuf {
+    std::env::temp_dir().join(format!(
+        "line_racing_{}_{}.json",
+        std::process::id(),
+        method
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn percentile_of_one_sample_is_that_sample() {
+        for p in [0.0, 50.0, 95.0, 99.0, 100.0] {
+            assert_eq!(percentile(&[7.0], p), 7.0);
+        }
+    }
+
+    #[test]
+    fn percentile_uses_the_nearest_rank() {
+        let odd = [1.0, 2.0, 3.0, 4.0, 5.0];
+        assert_eq!(percentile(&odd, 50.0), 3.0);
+        assert_eq!(percentile(&odd, 95.0), 5.0);
+        assert_eq!(percentile(&odd, 0.0), 1.0);
+
+        // Rank 2 of 4, the lower of the middle pair rather than their mean
+        let even = [1.0, 2.0, 3.0, 4.0];
+        assert_eq!(percentile(&even, 50.0), 2.0);
+        assert_eq!(percentile(&even, 75.0), 3.0);
+        assert_eq!(percentile(&even, 100.0), 4.0);
+    }
+
+    #[test]
+    fn p99_of_fewer_than_100_samples_is_the_max() {
+        let sorted = (1..=50).map(|i| i as f32).collect::<Vec<_>>();
+        assert_eq!(percentile(&sorted, 99.0), 50.0);
+        assert_eq!(percentile(&sorted, 95.0), 48.0);
+    }
+
+    #[test]
+    fn frame_stats_sort_the_frame_times() {
+        let stats = FrameStats::from_frame_times(&[4.0, 1.0, 3.0, 2.0]);
+        assert_eq!(stats.min, 1.0);
+        assert_eq!(stats.max, 4.0);
+        assert_eq!(stats.median, 2.0);
+        assert_eq!(stats.p99, 4.0);
+        assert_eq!(stats.mean, 2.5);
+    }
+
+    #[test]
+    fn frame_stats_of_no_frames_are_zero() {
+        let stats = FrameStats::from_frame_times(&[]);
+        assert_eq!(stats.mean, 0.0);
+        assert_eq!(stats.cv_percent(), 0.0);
+    }
+}
@@ -22,41 +22,130 @@ pub fn hash_noise(x: u32, y: u32, z: u32) -> f32 {
     unormf(uhash(x, (y << 11) + z))
 }
 
+/// The shape that keeps a [`ContinuousRandomLineGenerator`] from wandering off.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Bounds {
+    /// Axis aligned cube with half extents of the radius.
+    #[default]
+    Box,
+    Sphere,
+}
+
 pub struct ContinuousRandomLineGenerator {
     last_vert: Vec3,
+    center: Vec3,
     radius: f32,
     n: u32,
     length: f32,
+    seed_offset: u32,
+    bounds: Bounds,
 }
 
 impl Default for ContinuousRandomLineGenerator {
+    fn default() -> Self {
+        ContinuousRandomLineBuilder::default().build()
+    }
+}
+
+/// Settings for a [`ContinuousRandomLineGenerator`]. The default builds the
+/// same walk as [`ContinuousRandomLineGenerator::default`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ContinuousRandomLineBuilder {
+    pub seed: u32,
+    pub start: Vec3,
+    pub radius: f32,
+    pub step_length: f32,
+    pub bounds: Bounds,
+}
+
+impl Default for ContinuousRandomLineBuilder {
     fn default() -> Self {
         Self {
-            last_vert: Vec3::ZERO,
+            seed: 0,
+            start: Vec3::ZERO,
             radius: 1.0,
+            step_length: 0.03,
+            bounds: Bounds::Box,
+        }
+    }
+}
+
+impl ContinuousRandomLineBuilder {
+    pub fn with_seed(mut self, seed: u32) -> Self {
+        self.seed = seed;
+        self
+    }
+
+    pub fn with_start(mut self, start: Vec3) -> Self {
+        self.start = start;
+        self
+    }
+
+    pub fn with_radius(mut self, radius: f32) -> Self {
+        self.radius = radius;
+        self
+    }
+
+    pub fn with_step_length(mut self, step_length: f32) -> Self {
+        self.step_length = step_length;
+        self
+    }
+
+    pub fn with_bounds(mut self, bounds: Bounds) -> Self {
+        self.bounds = bounds;
+        self
+    }
+
+    pub fn build(&self) -> ContinuousRandomLineGenerator {
+        ContinuousRandomLineGenerator {
+            last_vert: self.start,
+            center: self.start,
+            radius: self.radius,
             n: 0,
-            length: 0.03,
+            length: self.step_length,
+            // uhash(0, 0) is 0, so seed 0 leaves the sequence unchanged
+            seed_offset: uhash(self.seed, 0),
+            bounds: self.bounds,
         }
     }
 }
 
 impl ContinuousRandomLineGenerator {
+    pub fn builder() -> ContinuousRandomLineBuilder {
+        ContinuousRandomLineBuilder::default()
+    }
+
     pub fn next_vert(&mut self) -> Vec3 {
+        let n = self.n ^ self.seed_offset;
         let mut noise = vec3(
-            hash_noise(self.n, 1, 0),
-            hash_noise(self.n, 2, 0),
-            hash_noise(self.n, 3, 0),
+            hash_noise(n, 1, 0),
+            hash_noise(n, 2, 0),
+            hash_noise(n, 3, 0),
         ) * 2.0
             - 1.0;
 
-        if self.last_vert.x.abs() > self.radius {
-            noise.x = noise.x.copysign(-self.last_vert.x.signum());
-        }
-        if self.last_vert.y.abs() > self.radius {
-            noise.y = noise.y.copysign(-self.last_vert.y.signum());
-        }
-        if self.last_vert.z.abs() > self.radius {
-            noise.z = noise.z.copysign(-self.last_vert.z.signum());
+        // Relative to the start, which the bounds are centered on
+        let offset = self.last_vert - self.center;
+        match self.bounds {
+            Bounds::Box => {
+                if offset.x.abs() > self.radius {
+                    noise.x = noise.x.copysign(-offset.x.signum());
+                }
+                if offset.y.abs() > self.radius {
+                    noise.y = noise.y.copysign(-offset.y.signum());
+                }
+                if offset.z.abs() > self.radius {
+                    noise.z = noise.z.copysign(-offset.z.signum());
+                }
+            }
+            Bounds::Sphere => {
+                // Mirror steps that would head further out back towards the center
+                let normal = offset.normalize_or_zero();
+                let outward = noise.dot(normal);
+                if offset.length() > self.radius && outward > 0.0 {
+                    noise -= 2.0 * outward * normal;
+                }
+            }
         }
 
         let next_offset = noise * self.length;
@@ -70,3 +159,229 @@ impl ContinuousRandomLineGenerator {
         (self.last_vert, self.next_vert())
     }
 }
+
+/// A stream of lines for the scenes to draw.
+pub trait LineSource {
+    fn next_line(&mut self) -> (Vec3, Vec3);
+
+    /// Next vertex of a continuous polyline. Sources whose lines aren't connected
+    /// just return the end of their next line.
+    fn next_vert(&mut self) -> Vec3 {
+        self.next_line().1
+    }
+}
+
+impl LineSource for ContinuousRandomLineGenerator {
+    fn next_line(&mut self) -> (Vec3, Vec3) {
+        ContinuousRandomLineGenerator::next_line(self)
+    }
+
+    fn next_vert(&mut self) -> Vec3 {
+        ContinuousRandomLineGenerator::next_vert(self)
+    }
+}
+
+/// The distribution of lines drawn by the scenes.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum LineSourceKind {
+    /// Short connected lines from a [`ContinuousRandomLineGenerator`].
+    #[default]
+    Walk,
+    /// Lines between two random points in the bounds, spanning much of the screen.
+    Long,
+    /// Edges of a wireframe grid filling the bounds.
+    Grid,
+    /// Lines radiating out from a few random centers.
+    Radial,
+    /// Short lines at random positions that don't share endpoints.
+    Disconnected,
+    /// Short lines packed into a small region around the start.
+    Clustered,
+}
+
+impl LineSourceKind {
+    /// Builds a source for `count` lines. Every source uses the seed, start,
+    /// radius, step length and bounds from `settings`.
+    pub fn build(self, settings: &ContinuousRandomLineBuilder, count: u32) -> LineGenerator {
+        let random = RandomLines {
+            center: settings.start,
+            radius: settings.radius,
+            length: settings.step_length,
+            bounds: settings.bounds,
+            n: 0,
+            seed_offset: uhash(settings.seed, 0),
+        };
+        match self {
+            LineSourceKind::Walk => LineGenerator::Walk(settings.build()),
+            LineSourceKind::Long => LineGenerator::Long(LongLineGenerator(random)),
+            LineSourceKind::Grid => LineGenerator::Grid(GridLineGenerator::new(settings, count)),
+            LineSourceKind::Radial => LineGenerator::Radial(RadialLineGenerator(random)),
+            LineSourceKind::Disconnected => {
+                LineGenerator::Disconnected(DisconnectedLineGenerator(random))
+            }
+            LineSourceKind::Clustered => {
+                LineGenerator::Disconnected(DisconnectedLineGenerator(RandomLines {
+                    radius: settings.radius * CLUSTER_SCALE,
+                    ..random
+                }))
+            }
+        }
+    }
+}
+
+/// One of the sources, built by [`LineSourceKind::build`]. An enum rather than a boxed
+/// [`LineSource`] so the loops generating millions of lines can inline the walk.
+pub enum LineGenerator {
+    Walk(ContinuousRandomLineGenerator),
+    Long(LongLineGenerator),
+    Grid(GridLineGenerator),
+    Radial(RadialLineGenerator),
+    Disconnected(DisconnectedLineGenerator),
+}
+
+impl LineGenerator {
+    #[inline(always)]
+    pub fn next_line(&mut self) -> (Vec3, Vec3) {
+        match self {
+            LineGenerator::Walk(source) => source.next_line(),
+            LineGenerator::Long(source) => source.next_line(),
+            LineGenerator::Grid(source) => source.next_line(),
+            LineGenerator::Radial(source) => source.next_line(),
+            LineGenerator::Disconnected(source) => source.next_line(),
+        }
+    }
+
+    #[inline(always)]
+    pub fn next_vert(&mut self) -> Vec3 {
+        match self {
+            LineGenerator::Walk(source) => source.next_vert(),
+            LineGenerator::Long(source) => source.next_vert(),
+            LineGenerator::Grid(source) => source.next_vert(),
+            LineGenerator::Radial(source) => source.next_vert(),
+            LineGenerator::Disconnected(source) => source.next_vert(),
+        }
+    }
+}
+
+/// Size of the clustered region relative to the bounds.
+const CLUSTER_SCALE: f32 = 0.1;
+
+/// Shared state for the sources that place each line independently.
+struct RandomLines {
+    center: Vec3,
+    radius: f32,
+    length: f32,
+    bounds: Bounds,
+    n: u32,
+    seed_offset: u32,
+}
+
+impl RandomLines {
+    /// Index of the next line, mixed with the seed.
+    fn next_index(&mut self) -> u32 {
+        let n = self.n ^ self.seed_offset;
+        self.n += 1;
+        n
+    }
+
+    /// Random point within the bounds.
+    fn point(&self, n: u32, channel: u32) -> Vec3 {
+        let p = match self.bounds {
+            Bounds::Box => signed_noise3(n, channel),
+            Bounds::Sphere => random_direction(n, channel) * hash_noise(n, channel + 3, 0).cbrt(),
+        };
+        self.center + p * self.radius
+    }
+}
+
+/// Three hash values in -1..1 from consecutive channels.
+fn signed_noise3(n: u32, channel: u32) -> Vec3 {
+    vec3(
+        hash_noise(n, channel, 0),
+        hash_noise(n, channel + 1, 0),
+        hash_noise(n, channel + 2, 0),
+    ) * 2.0
+        - 1.0
+}
+
+fn random_direction(n: u32, channel: u32) -> Vec3 {
+    signed_noise3(n, channel).try_normalize().unwrap_or(Vec3::X)
+}
+
+pub struct LongLineGenerator(RandomLines);
+
+impl LineSource for LongLineGenerator {
+    fn next_line(&mut self) -> (Vec3, Vec3) {
+        let n = self.0.next_index();
+        (self.0.point(n, 1), self.0.point(n, 7))
+    }
+}
+
+pub struct DisconnectedLineGenerator(RandomLines);
+
+impl LineSource for DisconnectedLineGenerator {
+    fn next_line(&mut self) -> (Vec3, Vec3) {
+        let n = self.0.next_index();
+        let start = self.0.point(n, 1);
+        (start, start + random_direction(n, 7) * self.0.length)
+    }
+}
+
+/// Number of centers the radial lines burst out of.
+const RADIAL_BURSTS: u32 = 8;
+
+pub struct RadialLineGenerator(RandomLines);
+
+impl LineSource for RadialLineGenerator {
+    fn next_line(&mut self) -> (Vec3, Vec3) {
+        let n = self.0.next_index();
+        let burst = n % RADIAL_BURSTS;
+        let center =
+            self.0.center + (self.0.point(burst ^ self.0.seed_offset, 13) - self.0.center) * 0.75;
+        let length = self.0.radius * 0.3 * hash_noise(n, 10, 0);
+        (center, center + random_direction(n, 1) * length)
+    }
+}
+
+/// Walks the edges of a lattice with just enough cells to fit all the lines.
+pub struct GridLineGenerator {
+    min: Vec3,
+    spacing: f32,
+    cells: u32,
+    n: u32,
+}
+
+impl GridLineGenerator {
+    fn new(settings: &ContinuousRandomLineBuilder, count: u32) -> Self {
+        // A lattice of k^3 cells has 3 * k * (k + 1)^2 edges
+        let mut cells = 1u32;
+        while 3 * cells as u64 * (cells as u64 + 1).pow(2) < count as u64 {
+            cells += 1;
+        }
+        Self {
+            min: settings.start - settings.radius,
+            spacing: settings.radius * 2.0 / cells as f32,
+            cells,
+            n: 0,
+        }
+    }
+}
+
+impl LineSource for GridLineGenerator {
+    fn next_line(&mut self) -> (Vec3, Vec3) {
+        let axis = (self.n % 3) as usize;
+        let i = self.n / 3;
+        self.n += 1;
+        let k = self.cells;
+        // Position along the axis, then the two coordinates of the edge across it
+        let along = i % k;
+        let a = (i / k) % (k + 1);
+        let b = (i / (k * (k + 1))) % (k + 1);
+        let mut cell = [a, b, along];
+        cell.rotate_right(axis + 1);
+        let start = self.min + Vec3::from_array(cell.map(|c| c as f32)) * self.spacing;
+        let mut end = start;
+        end[axis] += self.spacing;
+        (start, end)
+    }
+}
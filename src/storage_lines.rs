@@ -0,0 +1,416 @@
+//! Draws lines straight from a storage buffer in a custom render graph node, without any of
+//! Bevy's mesh, gizmo or shape pipelines. The vertex shader pulls the line endpoints by
+//! `vertex_index`, giving an upper bound for what a dedicated line renderer could reach.
+//!
+//! The lines take the same 24 bytes each as `bevy_lines_example_retained`, so the gap between
+//! the two is the overhead of the mesh pipeline. Buffers larger than the biggest storage binding
+//! the device allows (128 MiB, about 5.6 million lines, by default) are bound and drawn in
+//! slices, and the line count is capped at the biggest buffer the device allows. WebGL2 has no
+//! storage buffers in vertex shaders, so it doesn't run there.
+
+use bevy::{
+    asset::RenderAssetUsages,
+    core_pipeline::core_3d::{
+        graph::{Core3d, Node3d},
+        CORE_3D_DEPTH_FORMAT,
+    },
+    ecs::{
+        query::QueryItem,
+        system::{lifetimeless::SRes, SystemParamItem},
+    },
+    image::BevyDefault,
+    prelude::*,
+    render::{
+        camera::ExtractedCamera,
+        diagnostic::RecordDiagnostics,
+        extract_component::{ExtractComponent, ExtractComponentPlugin},
+        render_asset::{
+            AssetExtractionError, PrepareAssetError, RenderAsset, RenderAssetPlugin, RenderAssets,
+        },
+        render_graph::{
+            NodeRunError, RenderGraphContext, RenderGraphExt, RenderLabel, ViewNode, ViewNodeRunner,
+        },
+        render_resource::{
+            binding_types::{storage_buffer_read_only_sized, uniform_buffer},
+            *,
+        },
+        renderer::{RenderContext, RenderDevice},
+        view::{
+            ExtractedView, ViewDepthTexture, ViewTarget, ViewUniform, ViewUniformOffset,
+            ViewUniforms,
+        },
+        Render, RenderApp, RenderStartup, RenderSystems,
+    },
+    shader::Shader,
+};
+
+use crate::{
+    methods::{MethodKind, MethodRegistry},
+    LineGenSettings, RetainedLines, UpdateCountEvent,
+};
+
+pub fn register_methods(registry: &mut MethodRegistry) {
+    registry
+        .register(
+            "storage_buffer_retained",
+            MethodKind::Retained,
+            "Lines in one storage buffer, pulled by vertex_index in a custom render graph node",
+            storage_buffer_retained,
+        )
+        .with_max_line_count(GpuLineBuffer::max_lines);
+}
+
+pub struct StorageLinesPlugin;
+
+impl Plugin for StorageLinesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_asset::<LineBuffer>().add_plugins((
+            RenderAssetPlugin::<GpuLineBuffer>::default(),
+            ExtractComponentPlugin::<StorageLines>::default(),
+        ));
+
+        let Some(render_app) = app.get_sub_app_mut(RenderApp) else {
+            return;
+        };
+        render_app
+            .init_resource::<SpecializedRenderPipelines<StorageLinesPipeline>>()
+            .init_resource::<StorageLinesBindGroups>()
+            .add_systems(RenderStartup, init_storage_lines_pipeline)
+            .add_systems(
+                Render,
+                (
+                    prepare_storage_lines_pipelines.in_set(RenderSystems::Prepare),
+                    prepare_storage_lines_bind_groups.in_set(RenderSystems::PrepareBindGroups),
+                ),
+            )
+            .add_render_graph_node::<ViewNodeRunner<StorageLinesNode>>(Core3d, StorageLinesLabel)
+            // Drawn with the opaque meshes, so the lines are depth tested against them
+            .add_render_graph_edges(
+                Core3d,
+                (
+                    Node3d::MainOpaquePass,
+                    StorageLinesLabel,
+                    Node3d::MainTransmissivePass,
+                ),
+            );
+    }
+}
+
+/// Lines to upload into a storage buffer. Only kept in the render world once uploaded.
+#[derive(Asset, TypePath, Clone, Default)]
+pub struct LineBuffer {
+    pub lines: Vec<(Vec3, Vec3)>,
+}
+
+impl LineBuffer {
+    /// Three floats per endpoint, so a line takes as much space as in a `LineList` mesh.
+    pub const BYTES_PER_LINE: usize = 2 * 3 * size_of::<f32>();
+}
+
+/// Draws the lines of a [`LineBuffer`] in world space, ignoring any transform.
+#[derive(Component, Clone, ExtractComponent)]
+pub struct StorageLines(pub Handle<LineBuffer>);
+
+pub struct GpuLineBuffer {
+    pub buffer: Buffer,
+    /// Two vertices per line.
+    pub vertex_count: u32,
+}
+
+impl GpuLineBuffer {
+    /// Most lines that fit in one buffer on a device with `limits`.
+    pub fn max_lines(limits: &WgpuLimits) -> u32 {
+        (limits.max_buffer_size / LineBuffer::BYTES_PER_LINE as u64).min(u32::MAX as u64 / 2) as u32
+    }
+
+    /// Most lines in one storage binding. Bindings can only start at multiples of the offset
+    /// alignment, so they hold a multiple of that many lines.
+    pub fn max_lines_per_binding(limits: &WgpuLimits) -> u32 {
+        let alignment = limits.min_storage_buffer_offset_alignment;
+        let step = alignment as u64 * LineBuffer::BYTES_PER_LINE as u64;
+        (limits.max_storage_buffer_binding_size as u64 / step) as u32 * alignment
+    }
+
+    /// Splits the buffer into storage bindings of at most `max_lines` lines, each with the
+    /// number of lines in it.
+    pub fn bindings(&self, max_lines: u32) -> impl Iterator<Item = (BufferBinding<'_>, u32)> {
+        let line_count = self.vertex_count / 2;
+        (0..line_count)
+            .step_by(max_lines as usize)
+            .map(move |first| {
+                let lines = max_lines.min(line_count - first);
+                let binding = BufferBinding {
+                    buffer: &self.buffer,
+                    offset: first as u64 * LineBuffer::BYTES_PER_LINE as u64,
+                    size: BufferSize::new(lines as u64 * LineBuffer::BYTES_PER_LINE as u64),
+                };
+                (binding, lines)
+            })
+    }
+}
+
+impl RenderAsset for GpuLineBuffer {
+    type SourceAsset = LineBuffer;
+    type Param = SRes<RenderDevice>;
+
+    fn asset_usage(_source_asset: &Self::SourceAsset) -> RenderAssetUsages {
+        RenderAssetUsages::RENDER_WORLD
+    }
+
+    fn byte_len(source_asset: &Self::SourceAsset) -> Option<usize> {
+        Some(source_asset.lines.len() * LineBuffer::BYTES_PER_LINE)
+    }
+
+    // Leaves an empty buffer behind in the main world
+    fn take_gpu_data(
+        source: &mut Self::SourceAsset,
+        _previous_gpu_asset: Option<&Self>,
+    ) -> Result<Self::SourceAsset, AssetExtractionError> {
+        Ok(std::mem::take(source))
+    }
+
+    fn prepare_asset(
+        source_asset: Self::SourceAsset,
+        _asset_id: AssetId<Self::SourceAsset>,
+        render_device: &mut SystemParamItem<Self::Param>,
+        _previous_asset: Option<&Self>,
+    ) -> Result<Self, PrepareAssetError<Self::SourceAsset>> {
+        let contents = source_asset
+            .lines
+            .iter()
+            .flat_map(|(a, b)| [a.to_array(), b.to_array()])
+            .flatten()
+            .flat_map(f32::to_le_bytes)
+            .collect::<Vec<u8>>();
+        let buffer = render_device.create_buffer_with_data(&BufferInitDescriptor {
+            label: Some("line_buffer"),
+            contents: &contents,
+            // Also bound as the instance buffer of `InstancedLines`
+            usage: BufferUsages::STORAGE | BufferUsages::VERTEX,
+        });
+        Ok(GpuLineBuffer {
+            buffer,
+            vertex_count: source_asset.lines.len() as u32 * 2,
+        })
+    }
+}
+
+#[derive(Resource)]
+struct StorageLinesPipeline {
+    view_layout: BindGroupLayoutDescriptor,
+    lines_layout: BindGroupLayoutDescriptor,
+    shader: Handle<Shader>,
+}
+
+fn init_storage_lines_pipeline(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(StorageLinesPipeline {
+        view_layout: BindGroupLayoutDescriptor::new(
+            "storage_lines_view_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                uniform_buffer::<ViewUniform>(true),
+            ),
+        ),
+        lines_layout: BindGroupLayoutDescriptor::new(
+            "storage_lines_layout",
+            &BindGroupLayoutEntries::single(
+                ShaderStages::VERTEX,
+                storage_buffer_read_only_sized(false, None),
+            ),
+        ),
+        shader: asset_server.load("shaders/storage_lines.wgsl"),
+    });
+}
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+struct StorageLinesPipelineKey {
+    hdr: bool,
+    samples: u32,
+}
+
+impl SpecializedRenderPipeline for StorageLinesPipeline {
+    type Key = StorageLinesPipelineKey;
+
+    fn specialize(&self, key: Self::Key) -> RenderPipelineDescriptor {
+        RenderPipelineDescriptor {
+            label: Some("storage_lines_pipeline".into()),
+            layout: vec![self.view_layout.clone(), self.lines_layout.clone()],
+            vertex: VertexState {
+                shader: self.shader.clone(),
+                ..default()
+            },
+            primitive: PrimitiveState {
+                topology: PrimitiveTopology::LineList,
+                ..default()
+            },
+            depth_stencil: Some(DepthStencilState {
+                format: CORE_3D_DEPTH_FORMAT,
+                depth_write_enabled: true,
+                depth_compare: CompareFunction::GreaterEqual,
+                stencil: StencilState::default(),
+                bias: DepthBiasState::default(),
+            }),
+            multisample: MultisampleState {
+                count: key.samples,
+                ..default()
+            },
+            fragment: Some(FragmentState {
+                shader: self.shader.clone(),
+                targets: vec![Some(ColorTargetState {
+                    format: if key.hdr {
+                        ViewTarget::TEXTURE_FORMAT_HDR
+                    } else {
+                        TextureFormat::bevy_default()
+                    },
+                    blend: None,
+                    write_mask: ColorWrites::ALL,
+                })],
+                ..default()
+            }),
+            ..default()
+        }
+    }
+}
+
+#[derive(Component)]
+struct StorageLinesPipelineId(CachedRenderPipelineId);
+
+fn prepare_storage_lines_pipelines(
+    mut commands: Commands,
+    pipeline_cache: Res<PipelineCache>,
+    mut pipelines: ResMut<SpecializedRenderPipelines<StorageLinesPipeline>>,
+    pipeline: Res<StorageLinesPipeline>,
+    views: Query<(Entity, &ExtractedView, &Msaa)>,
+) {
+    for (entity, view, msaa) in &views {
+        let pipeline_id = pipelines.specialize(
+            &pipeline_cache,
+            &pipeline,
+            StorageLinesPipelineKey {
+                hdr: view.hdr,
+                samples: msaa.samples(),
+            },
+        );
+        commands
+            .entity(entity)
+            .insert(StorageLinesPipelineId(pipeline_id));
+    }
+}
+
+/// Bind groups of the views and of every slice of the line buffers to draw this frame.
+#[derive(Resource, Default)]
+struct StorageLinesBindGroups {
+    view: Option<BindGroup>,
+    lines: Vec<(BindGroup, u32)>,
+}
+
+fn prepare_storage_lines_bind_groups(
+    mut bind_groups: ResMut<StorageLinesBindGroups>,
+    pipeline: Res<StorageLinesPipeline>,
+    pipeline_cache: Res<PipelineCache>,
+    view_uniforms: Res<ViewUniforms>,
+    render_device: Res<RenderDevice>,
+    line_buffers: Res<RenderAssets<GpuLineBuffer>>,
+    storage_lines: Query<&StorageLines>,
+) {
+    bind_groups.view = view_uniforms.uniforms.binding().map(|view_uniforms| {
+        render_device.create_bind_group(
+            "storage_lines_view_bind_group",
+            &pipeline_cache.get_bind_group_layout(&pipeline.view_layout),
+            &BindGroupEntries::single(view_uniforms),
+        )
+    });
+    // Buffers can be bigger than a storage binding may be, so they're drawn a slice at a time
+    let max_lines = GpuLineBuffer::max_lines_per_binding(&render_device.limits());
+    let layout = pipeline_cache.get_bind_group_layout(&pipeline.lines_layout);
+    bind_groups.lines = storage_lines
+        .iter()
+        .filter_map(|lines| line_buffers.get(&lines.0))
+        .flat_map(|line_buffer| line_buffer.bindings(max_lines))
+        .map(|(binding, lines)| {
+            let bind_group = render_device.create_bind_group(
+                "storage_lines_bind_group",
+                &layout,
+                &BindGroupEntries::single(binding),
+            );
+            (bind_group, lines * 2)
+        })
+        .collect();
+}
+
+#[derive(Debug, Hash, PartialEq, Eq, Clone, RenderLabel)]
+struct StorageLinesLabel;
+
+#[derive(Default)]
+struct StorageLinesNode;
+
+impl ViewNode for StorageLinesNode {
+    type ViewQuery = (
+        &'static ExtractedCamera,
+        &'static ViewTarget,
+        &'static ViewDepthTexture,
+        &'static ViewUniformOffset,
+        &'static StorageLinesPipelineId,
+    );
+
+    fn run(
+        &self,
+        _graph: &mut RenderGraphContext,
+        render_context: &mut RenderContext,
+        (camera, target, depth, view_uniform_offset, pipeline_id): QueryItem<Self::ViewQuery>,
+        world: &World,
+    ) -> Result<(), NodeRunError> {
+        let bind_groups = world.resource::<StorageLinesBindGroups>();
+        let Some(view_bind_group) = &bind_groups.view else {
+            return Ok(());
+        };
+        if bind_groups.lines.is_empty() {
+            return Ok(());
+        }
+        let Some(pipeline) = world
+            .resource::<PipelineCache>()
+            .get_render_pipeline(pipeline_id.0)
+        else {
+            return Ok(());
+        };
+
+        let diagnostics = render_context.diagnostic_recorder();
+        let mut render_pass = render_context.begin_tracked_render_pass(RenderPassDescriptor {
+            label: Some("storage_lines_pass"),
+            color_attachments: &[Some(target.get_color_attachment())],
+            depth_stencil_attachment: Some(depth.get_attachment(StoreOp::Store)),
+            timestamp_writes: None,
+            occlusion_query_set: None,
+        });
+        let pass_span = diagnostics.pass_span(&mut render_pass, "storage_lines_pass");
+        if let Some(viewport) = camera.viewport.as_ref() {
+            render_pass.set_camera_viewport(viewport);
+        }
+
+        render_pass.set_render_pipeline(pipeline);
+        render_pass.set_bind_group(0, view_bind_group, &[view_uniform_offset.offset]);
+        for (bind_group, vertex_count) in &bind_groups.lines {
+            render_pass.set_bind_group(1, bind_group, &[]);
+            render_pass.draw(0..*vertex_count, 0..1);
+        }
+
+        pass_span.end(&mut render_pass);
+        Ok(())
+    }
+}
+
+pub fn storage_buffer_retained(
+    mut commands: Commands,
+    mut line_buffers: ResMut<Assets<LineBuffer>>,
+    mut update_count_event: MessageReader<UpdateCountEvent>,
+    line_gen_settings: Res<LineGenSettings>,
+) {
+    let Some(count) = update_count_event.read().last() else {
+        return;
+    };
+    let mut line_gen = line_gen_settings.generator(count.0);
+    let lines = (0..count.0).map(|_| line_gen.next_line()).collect();
+    commands
+        .spawn(StorageLines(line_buffers.add(LineBuffer { lines })))
+        .insert(RetainedLines);
+}
@@ -0,0 +1,161 @@
+//! Optional breakdown of the frame time into main world CPU time, render world prepare time
+//! and GPU time of the main 3D passes, to tell CPU bound methods from GPU bound ones.
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use bevy::{
+    diagnostic::{DiagnosticPath, DiagnosticsStore},
+    platform::time::Instant as DiagnosticInstant,
+    prelude::*,
+    render::{diagnostic::RenderDiagnosticsPlugin, Render, RenderApp, RenderSystems},
+};
+
+/// GPU time of the passes the lines are drawn in. Only recorded when the adapter supports
+/// timestamp queries.
+const GPU_PASS_PATHS: [DiagnosticPath; 6] = [
+    DiagnosticPath::const_new("render/main_opaque_pass_3d/elapsed_gpu"),
+    DiagnosticPath::const_new("render/storage_lines_pass/elapsed_gpu"),
+    DiagnosticPath::const_new("render/instanced_lines_pass/elapsed_gpu"),
+    DiagnosticPath::const_new("render/compute_lines_rasterize/elapsed_gpu"),
+    DiagnosticPath::const_new("render/compute_lines_blit/elapsed_gpu"),
+    DiagnosticPath::const_new("render/main_transparent_pass_3d/elapsed_gpu"),
+];
+
+pub struct FrameTimingPlugin;
+
+impl Plugin for FrameTimingPlugin {
+    fn build(&self, app: &mut App) {
+        let prepare_time = RenderPrepareTime::default();
+        app.add_plugins(RenderDiagnosticsPlugin)
+            .insert_resource(prepare_time.clone())
+            .init_resource::<FrameTimings>()
+            .add_systems(First, start_main_timer)
+            .add_systems(Last, record_frame_timings);
+
+        if let Some(render_app) = app.get_sub_app_mut(RenderApp) {
+            render_app
+                .insert_resource(prepare_time)
+                .init_resource::<PrepareStart>()
+                .add_systems(
+                    Render,
+                    (
+                        start_prepare_timer
+                            .after(RenderSystems::ExtractCommands)
+                            .before(RenderSystems::PrepareAssets),
+                        end_prepare_timer
+                            .after(RenderSystems::PrepareBindGroups)
+                            .before(RenderSystems::Render),
+                    ),
+                );
+        }
+    }
+}
+
+/// Per-frame timings in milliseconds, recorded while a benchmark is running.
+#[derive(Resource, Default)]
+pub struct FrameTimings {
+    recording: bool,
+    main_started: Option<Instant>,
+    samples: FrameTimingSamples,
+}
+
+impl FrameTimings {
+    pub fn start(&mut self) {
+        self.recording = true;
+        self.samples = FrameTimingSamples::default();
+    }
+
+    pub fn finish(&mut self) -> FrameTimingSamples {
+        self.recording = false;
+        std::mem::take(&mut self.samples)
+    }
+}
+
+#[derive(Clone, Default, Debug)]
+pub struct FrameTimingSamples {
+    /// Time spent running the main world schedule.
+    pub cpu_ms: Vec<f32>,
+    /// Time the render world spends preparing and queuing before the render graph runs.
+    pub prepare_ms: Vec<f32>,
+    /// GPU time of the main 3D passes. Empty when timestamp queries aren't supported.
+    pub gpu_ms: Vec<f32>,
+}
+
+impl FrameTimingSamples {
+    pub fn mean_cpu_ms(&self) -> Option<f32> {
+        mean(&self.cpu_ms)
+    }
+
+    pub fn mean_prepare_ms(&self) -> Option<f32> {
+        mean(&self.prepare_ms)
+    }
+
+    pub fn mean_gpu_ms(&self) -> Option<f32> {
+        mean(&self.gpu_ms)
+    }
+}
+
+fn mean(values: &[f32]) -> Option<f32> {
+    (!values.is_empty()).then(|| values.iter().sum::<f32>() / values.len() as f32)
+}
+
+/// Latest render world prepare time, shared between the main and render worlds since they
+/// can run in parallel.
+#[derive(Resource, Clone, Default)]
+struct RenderPrepareTime(Arc<Mutex<Option<f32>>>);
+
+#[derive(Resource, Default)]
+struct PrepareStart(Option<Instant>);
+
+fn start_main_timer(mut timings: ResMut<FrameTimings>) {
+    timings.main_started = Some(Instant::now());
+}
+
+fn record_frame_timings(
+    mut timings: ResMut<FrameTimings>,
+    prepare_time: Res<RenderPrepareTime>,
+    diagnostics: Res<DiagnosticsStore>,
+    mut last_gpu_measurement: Local<Option<DiagnosticInstant>>,
+) {
+    let prepare_ms = prepare_time.0.lock().unwrap().take();
+    // GPU timings are read back a few frames late and only show up in the store when they arrive
+    let gpu_measurements = GPU_PASS_PATHS
+        .iter()
+        .filter_map(|path| diagnostics.get(path)?.measurement())
+        .collect::<Vec<_>>();
+    let new_gpu_measurement = gpu_measurements
+        .first()
+        .filter(|measurement| Some(measurement.time) != *last_gpu_measurement);
+    let gpu_ms =
+        new_gpu_measurement.map(|_| gpu_measurements.iter().map(|m| m.value as f32).sum::<f32>());
+    if let Some(measurement) = new_gpu_measurement {
+        *last_gpu_measurement = Some(measurement.time);
+    }
+
+    if !timings.recording {
+        return;
+    }
+    if let Some(started) = timings.main_started {
+        let cpu_ms = started.elapsed().as_secs_f32() * 1000.0;
+        timings.samples.cpu_ms.push(cpu_ms);
+    }
+    if let Some(prepare_ms) = prepare_ms {
+        timings.samples.prepare_ms.push(prepare_ms);
+    }
+    if let Some(gpu_ms) = gpu_ms {
+        timings.samples.gpu_ms.push(gpu_ms);
+    }
+}
+
+fn start_prepare_timer(mut start: ResMut<PrepareStart>) {
+    start.0 = Some(Instant::now());
+}
+
+fn end_prepare_timer(start: Res<PrepareStart>, prepare_time: Res<RenderPrepareTime>) {
+    if let Some(start) = start.0 {
+        *prepare_time.0.lock().unwrap() = Some(start.elapsed().as_secs_f32() * 1000.0);
+    }
+}
@@ -0,0 +1,262 @@
+//! Finds the highest line count a method can draw within a frame budget.
+
+use bevy::{diagnostic::FrameCount, prelude::*};
+
+use crate::{
+    memory::MemoryUsage, BenchmarkName, LineCount, MaxLineCount, RetainedLines, UpdateCountEvent,
+};
+
+/// Tune the line count before benchmarking.
+#[derive(Resource)]
+pub struct AutoCount {
+    pub frame_budget_ms: f32,
+    /// Resident memory the process may grow to, in bytes. Unlimited if unknown.
+    pub memory_limit: Option<u64>,
+    /// Set when the tuner stopped at the memory limit rather than the frame budget.
+    pub memory_bound: bool,
+}
+
+/// Set once the tuner has settled on a line count.
+#[derive(Resource)]
+pub struct CountStable(pub bool);
+
+/// Line count the tuner starts at.
+pub const START_LINE_COUNT: u32 = 50_000;
+
+/// Fewest lines the tuner settles on, even if they're over the budget.
+const MIN_LINE_COUNT: u32 = 1_000;
+
+/// Frames to wait after changing the line count, so the new lines are uploaded and
+/// pipelines have compiled before measuring.
+const SETTLE_FRAMES: u32 = 20;
+
+/// Frames to take the median frame time of at each line count.
+const MEASURE_FRAMES: usize = 30;
+
+/// Stop bisecting once the bracket is narrower than this fraction of the upper bound.
+const TOLERANCE: f32 = 0.02;
+
+#[derive(Default)]
+pub struct TunerState {
+    /// Highest line count measured within the budget.
+    within: Option<u32>,
+    /// Lowest line count measured over the budget. Until one is found the count doubles.
+    over: Option<u32>,
+    /// Set once the final count is picked, which may need one more respawn.
+    finished: bool,
+    updated_frame: u32,
+    frame_times_ms: Vec<f32>,
+}
+
+/// What the tuner does after measuring a line count.
+#[derive(Debug, PartialEq)]
+enum Step {
+    /// Measure this many lines next.
+    Measure(u32),
+    /// Settle on this many lines. `memory_bound` when the cap stopped the search rather than
+    /// the frame budget.
+    Finish { count: u32, memory_bound: bool },
+}
+
+impl TunerState {
+    /// Records whether `line_count` lines took `frame_time_ms` within the budget, and picks
+    /// the next step. Fails when not even [`MIN_LINE_COUNT`] lines fit under `memory_cap`.
+    fn step(
+        &mut self,
+        line_count: u32,
+        frame_time_ms: f32,
+        frame_budget_ms: f32,
+        memory_cap: u32,
+    ) -> Result<Step, u32> {
+        if memory_cap < MIN_LINE_COUNT {
+            return Err(memory_cap);
+        }
+        if frame_time_ms <= frame_budget_ms {
+            self.within = Some(line_count);
+        } else {
+            self.over = Some(line_count);
+        }
+        let within = self.within.unwrap_or(0);
+        let memory_bound = memory_cap as f32 <= within as f32 * (1.0 + TOLERANCE);
+        let next_count = match self.over {
+            _ if memory_bound => None,
+            None => Some(line_count.saturating_mul(2).min(memory_cap)),
+            Some(over) if over - within <= (over as f32 * TOLERANCE) as u32 => None,
+            Some(over) if over <= MIN_LINE_COUNT => None,
+            Some(over) => Some((within + (over - within) / 2).min(memory_cap)),
+        };
+        Ok(match next_count {
+            Some(count) => Step::Measure(count),
+            None => {
+                self.finished = true;
+                // Even the starting count may be over the budget, so keep a few lines
+                Step::Finish {
+                    count: within.min(memory_cap).max(MIN_LINE_COUNT),
+                    memory_bound,
+                }
+            }
+        })
+    }
+}
+
+/// Doubles the line count until the frame time is over the budget, then bisects between the
+/// last count within the budget and the first count over it. Never grows past the count
+/// expected to fit in the memory limit, or past the most lines the method can draw, and
+/// exits with an error if fewer than [`MIN_LINE_COUNT`] lines fit.
+#[allow(clippy::too_many_arguments)]
+pub fn line_count_tuner(
+    mut commands: Commands,
+    mut update_count_event: MessageWriter<UpdateCountEvent>,
+    mut app_exit: MessageWriter<AppExit>,
+    benchmark_name: Res<BenchmarkName>,
+    mut line_count: ResMut<LineCount>,
+    max_line_count: Res<MaxLineCount>,
+    time: Res<Time>,
+    mut window: Option<Single<&mut Window>>,
+    retained_lines: Query<Entity, With<RetainedLines>>,
+    mut count_stable: ResMut<CountStable>,
+    frame_count: Res<FrameCount>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut memory: ResMut<MemoryUsage>,
+    auto_count: Option<ResMut<AutoCount>>,
+    mut state: Local<TunerState>,
+) {
+    let Some(mut auto_count) = auto_count else {
+        return;
+    };
+    if count_stable.0 || state.updated_frame + SETTLE_FRAMES > frame_count.0 {
+        return;
+    }
+    if state.finished {
+        count_stable.0 = true;
+        return;
+    }
+    state.frame_times_ms.push(time.delta_secs() * 1000.0);
+    if state.frame_times_ms.len() < MEASURE_FRAMES {
+        return;
+    }
+    let frame_time_ms = median(&mut state.frame_times_ms);
+    state.frame_times_ms.clear();
+
+    // Running out of room in a buffer counts as running out of memory
+    let memory_cap = auto_count
+        .memory_limit
+        .and_then(|limit| memory.max_line_count(line_count.0, limit))
+        .unwrap_or(u32::MAX / 2)
+        .min(max_line_count.0);
+
+    let step = state.step(
+        line_count.0,
+        frame_time_ms,
+        auto_count.frame_budget_ms,
+        memory_cap,
+    );
+    let next_count = match step {
+        Ok(Step::Measure(count)) => count,
+        Ok(Step::Finish {
+            count,
+            memory_bound,
+        }) => {
+            auto_count.memory_bound = memory_bound;
+            if count == line_count.0 {
+                count_stable.0 = true;
+                return;
+            }
+            count
+        }
+        Err(memory_cap) => {
+            eprintln!(
+                "{} can only fit {memory_cap} lines within the memory limit, fewer than the {MIN_LINE_COUNT} the tuner needs",
+                benchmark_name.0
+            );
+            state.finished = true;
+            app_exit.write(AppExit::error());
+            return;
+        }
+    };
+
+    line_count.0 = next_count;
+    update_count_event.write(UpdateCountEvent(line_count.0));
+    state.updated_frame = frame_count.0;
+    if let Some(window) = window.as_mut() {
+        window.title = format!("{}: {} lines", benchmark_name.0, line_count.0);
+    }
+    for entity in retained_lines.iter() {
+        commands.entity(entity).despawn();
+    }
+    memory.clear();
+    let mesh_hs = meshes.iter().map(|(h, _)| h).collect::<Vec<_>>();
+    for mesh in &mesh_hs {
+        meshes.remove(*mesh);
+    }
+}
+
+fn median(values: &mut [f32]) -> f32 {
+    values.sort_by(f32::total_cmp);
+    values[values.len() / 2]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the tuner against frame times that grow linearly with the line count, returning
+    /// the count it settles on and whether it was memory bound.
+    fn tune(ms_per_line: f32, frame_budget_ms: f32, memory_cap: u32) -> Result<(u32, bool), u32> {
+        let mut state = TunerState::default();
+        let mut line_count = START_LINE_COUNT;
+        for _ in 0..100 {
+            let frame_time_ms = line_count as f32 * ms_per_line;
+            match state.step(line_count, frame_time_ms, frame_budget_ms, memory_cap)? {
+                Step::Measure(count) => line_count = count,
+                Step::Finish {
+                    count,
+                    memory_bound,
+                } => return Ok((count, memory_bound)),
+            }
+        }
+        panic!("the tuner didn't settle");
+    }
+
+    #[test]
+    fn converges_within_the_tolerance_below_the_budget() {
+        // 1M lines fit in the 10ms budget
+        let (count, memory_bound) = tune(1e-5, 10.0, u32::MAX / 2).unwrap();
+        assert!(count <= 1_000_000, "{count} is over the budget");
+        assert!(
+            count as f32 >= 1_000_000.0 * (1.0 - 2.0 * TOLERANCE),
+            "{count} is too low"
+        );
+        assert!(!memory_bound);
+    }
+
+    #[test]
+    fn settles_on_the_fewest_lines_when_even_those_are_over_the_budget() {
+        let (count, memory_bound) = tune(1.0, 10.0, u32::MAX / 2).unwrap();
+        assert_eq!(count, MIN_LINE_COUNT);
+        assert!(!memory_bound);
+    }
+
+    #[test]
+    fn stops_at_the_max_line_count() {
+        // Well within the budget, but the buffer only has room for 300k lines
+        let (count, memory_bound) = tune(1e-9, 10.0, 300_000).unwrap();
+        assert_eq!(count, 300_000);
+        assert!(memory_bound);
+    }
+
+    #[test]
+    fn stops_at_the_memory_cap_below_the_budget() {
+        let (count, memory_bound) = tune(1e-5, 10.0, 120_000).unwrap();
+        assert_eq!(count, 120_000);
+        assert!(memory_bound);
+    }
+
+    #[test]
+    fn fails_when_the_fewest_lines_are_over_the_memory_cap() {
+        assert_eq!(
+            tune(1e-5, 10.0, MIN_LINE_COUNT - 1),
+            Err(MIN_LINE_COUNT - 1)
+        );
+    }
+}